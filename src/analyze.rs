@@ -0,0 +1,282 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use image::{imageops::FilterType, RgbaImage};
+use serde::Serialize;
+
+use crate::{
+    automation::{Automation, Coordinate, Region, ScaleRange},
+    cancel::CancellationToken,
+    config::Config,
+    error::SrPlotResult,
+    event::{EventBus, PlotEvent},
+    input::InputBackend,
+    plot::ImageFile,
+    screenshot::{FrameSource, GameWindow},
+    target::{scale_range, Target},
+    tr,
+    utils::WindowMatcher,
+};
+
+/// 一个模板在截图中的匹配结果
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateScore {
+    pub template: String,
+    pub threshold: f64,
+    /// 最高的相似度，截图为纯色时不是有限值
+    pub score: f64,
+    /// 相似度最高的位置，坐标相对于窗口左上角
+    pub location: Coordinate,
+    pub matched: bool,
+}
+
+/// 根据截图将会采取的操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum Decision {
+    /// 不在对话中，不做任何操作
+    None,
+    /// 在对话中但没有选项，在鼠标当前位置点击
+    Continue,
+    /// 选择第 `index` 个选项（从 0 开始），`key` 不为空时按键选择，否则点击 `(x, y)`
+    Choose {
+        index: usize,
+        count: usize,
+        key: Option<char>,
+        x: u32,
+        y: u32,
+    },
+}
+
+/// 一张截图的识别结果
+#[derive(Debug, Clone, Serialize)]
+pub struct Analysis {
+    pub file: PathBuf,
+    /// 截图文件的大小
+    pub image_size: (u32, u32),
+    /// 模拟的窗口大小
+    pub window_size: (u32, u32),
+    /// 截图缩小到 1080p 的比例
+    pub screenshot_factor: f64,
+    /// 模板的缩放范围，窗口不小于 1080p 时不缩放
+    pub scale_range: Option<ScaleRange>,
+    /// 按匹配顺序排列，找到对话图标后不再匹配其余的对话模板
+    pub templates: Vec<TemplateScore>,
+    pub decision: Decision,
+}
+
+/// 解析 `1920x1080` 形式的窗口大小
+pub fn parse_size(s: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("invalid size: {}, expected WIDTHxHEIGHT", s);
+    let (width, height) = s.split_once(['x', 'X', '*']).ok_or_else(invalid)?;
+    let width: u32 = width.trim().parse().map_err(|_| invalid())?;
+    let height: u32 = height.trim().parse().map_err(|_| invalid())?;
+    if width == 0 || height == 0 {
+        return Err(invalid());
+    }
+    Ok((width, height))
+}
+
+/// 始终返回同一张图片的画面来源
+struct StillFrame(RgbaImage);
+
+impl FrameSource for StillFrame {
+    fn find_windows(&self, _: &WindowMatcher) -> Vec<GameWindow> {
+        Vec::new()
+    }
+
+    fn capture(&self, _: &GameWindow) -> SrPlotResult<RgbaImage> {
+        Ok(self.0.clone())
+    }
+}
+
+/// 不发送任何输入，分析时只在 dry-run 模式下使用
+struct NoInput;
+
+impl InputBackend for NoInput {
+    fn click(&self) -> SrPlotResult<()> {
+        Ok(())
+    }
+
+    fn release(&self) -> SrPlotResult<()> {
+        Ok(())
+    }
+
+    fn position(&self) -> (u32, u32) {
+        (0, 0)
+    }
+
+    fn move_mouse(&self, _: u32, _: u32) -> SrPlotResult<()> {
+        Ok(())
+    }
+
+    fn press_key(&self, _: char) -> SrPlotResult<()> {
+        Ok(())
+    }
+}
+
+/// 把截图当作大小为 `window_size` 的前台窗口的画面，按自动对话的流程识别一次，不发送任何输入
+///
+/// 截图大小与窗口不同时先缩放到窗口大小，不设置 `window_size` 时使用截图的大小
+pub(crate) fn analyze(
+    path: &Path,
+    window_size: Option<(u32, u32)>,
+    config: &Config,
+    select_img: &ImageFile,
+    start_img: &[ImageFile],
+) -> SrPlotResult<Analysis> {
+    let image = image::open(path)?.to_rgba8();
+    let image_size = image.dimensions();
+    let (width, height) = window_size.unwrap_or(image_size);
+    let frame = if (width, height) == image_size {
+        image
+    } else {
+        image::imageops::resize(&image, width, height, FilterType::Triangle)
+    };
+
+    let window = GameWindow {
+        id: 0,
+        title: path.display().to_string(),
+        pid: 0,
+        region: Region::new(0, 0, width, height),
+        is_active: true,
+    };
+    let events = EventBus::new();
+    let receiver = events.subscribe();
+    let mut auto = Automation::new(
+        window.id,
+        Arc::new(StillFrame(frame)),
+        Arc::new(NoInput),
+        events.clone(),
+        CancellationToken::new(),
+    );
+    auto.set_dry_run(true);
+    let mut target = Target::new(window, auto, events);
+    target.autoplot(config, select_img, start_img)?;
+
+    let mut templates = Vec::new();
+    let mut decision = Decision::None;
+    for event in receiver.try_iter() {
+        match event {
+            PlotEvent::TemplateMatched {
+                template,
+                score,
+                location,
+                ..
+            } => {
+                let threshold = if template == select_img.0 {
+                    config.select_threshold
+                } else {
+                    config.start_threshold
+                };
+                templates.push(TemplateScore {
+                    matched: score.is_finite() && score >= threshold,
+                    template,
+                    threshold,
+                    score,
+                    location,
+                });
+            }
+            PlotEvent::DialogueStarted { .. } => decision = Decision::Continue,
+            PlotEvent::ChoiceDetected {
+                candidates, chosen, ..
+            } => {
+                let ((left, top), (right, bottom)) = candidates[chosen].coordinate;
+                decision = Decision::Choose {
+                    index: chosen,
+                    count: candidates.len(),
+                    key: config.choice_key,
+                    x: (left + right) / 2,
+                    y: (top + bottom) / 2,
+                };
+            }
+            _ => {}
+        }
+    }
+
+    Ok(Analysis {
+        file: path.to_path_buf(),
+        image_size,
+        window_size: (width, height),
+        screenshot_factor: target.auto.screenshot_factor(),
+        scale_range: scale_range(width),
+        templates,
+        decision,
+    })
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.file.display())?;
+        writeln!(
+            f,
+            "  {}",
+            tr!(
+                "analyze.summary",
+                image_width = self.image_size.0,
+                image_height = self.image_size.1,
+                window_width = self.window_size.0,
+                window_height = self.window_size.1,
+                factor = format!("{:.2}", self.screenshot_factor),
+                scale = match self.scale_range {
+                    Some((start, end)) => format!("{:.2}~{:.2}", start, end),
+                    None => tr!("analyze.no_scale"),
+                }
+            )
+        )?;
+        for template in &self.templates {
+            let ((left, top), (right, bottom)) = template.location;
+            writeln!(
+                f,
+                "  {}",
+                tr!(
+                    "analyze.template",
+                    template = template.template,
+                    score = format!("{:.3}", template.score),
+                    threshold = format!("{:.2}", template.threshold),
+                    result = tr!(if template.matched {
+                        "analyze.matched"
+                    } else {
+                        "analyze.unmatched"
+                    }),
+                    left = left,
+                    top = top,
+                    right = right,
+                    bottom = bottom
+                )
+            )?;
+        }
+        let decision = match self.decision {
+            Decision::None => tr!("analyze.none"),
+            Decision::Continue => tr!("analyze.continue"),
+            Decision::Choose {
+                index,
+                count,
+                key: Some(key),
+                ..
+            } => tr!(
+                "analyze.choose_key",
+                count = count,
+                key = key,
+                index = index + 1
+            ),
+            Decision::Choose {
+                index,
+                count,
+                key: None,
+                x,
+                y,
+            } => tr!(
+                "analyze.choose_click",
+                count = count,
+                index = index + 1,
+                x = x,
+                y = y
+            ),
+        };
+        write!(f, "  {}", decision)
+    }
+}
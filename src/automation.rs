@@ -1,5 +1,6 @@
 use std::collections::HashMap;
-use std::time::Instant;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use image::codecs::bmp::BmpEncoder;
 use image::{ImageBuffer, Rgba};
@@ -10,17 +11,32 @@ use opencv::{
     core::Point,
     imgcodecs::{imdecode, ImreadModes},
 };
+use serde::Deserialize;
 
-use crate::plot::CropRatio;
+use crate::plot::{CropRatio, ImageFile};
 use crate::{
+    cancel::CancellationToken,
     error::{SrPlotError, SrPlotResult},
-    input::Input,
-    screenshot,
+    event::{EventBus, PlotEvent, WindowId},
+    governor::{frame_signature, GovernorConfig, InputGovernor},
+    humanize::{HumanizeConfig, Humanizer},
+    input::InputBackend,
+    recorder::{Action, Detection, FlightRecorder, RecorderConfig},
+    screenshot::{self, FrameSource, GameWindow},
+    stats::Stats,
+    tr,
 };
 
 pub type ScaleRange = (f64, f64);
 pub type Coordinate = ((u32, u32), (u32, u32));
 
+/// 一次匹配中找到的目标
+#[derive(Debug, Clone, Copy)]
+pub struct Candidate {
+    pub coordinate: Coordinate,
+    pub score: f64,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Region {
     pub x: u32,
@@ -40,32 +56,150 @@ impl Region {
     }
 }
 
+/// 点击时鼠标不在游戏窗口内的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MouseOutsidePolicy {
+    /// 返回错误
+    #[default]
+    Error,
+    /// 先把鼠标移动到窗口内的安全位置再点击
+    Move,
+    /// 跳过点击，等待鼠标回到窗口内
+    Wait,
+}
+
+/// 默认的安全位置：对话文字所在的区域，点击不会选中选项
+pub const SAFE_POINT: (f32, f32) = (0.5, 0.85);
+
 pub struct Automation {
+    window_id: WindowId,
     screenshot_mat: Option<Mat>,
     screenshot_pos: Option<Region>,
     screenshot_factor: f64,
-    window_title: String,
     window_region: Option<Region>,
+    /// 最近一次截图的窗口
+    window: Option<GameWindow>,
     cache: HashMap<String, Mat>,
+    dry_run: bool,
+    restore_cursor: bool,
+    mouse_outside: MouseOutsidePolicy,
+    safe_point: (f32, f32),
+    /// 已经输出过等待鼠标回到窗口内的日志
+    waiting_for_mouse: bool,
+    stats: Stats,
+    recorder: FlightRecorder,
+    humanizer: Humanizer,
+    governor: InputGovernor,
+    /// 正在重试没有生效的点击
+    retry: bool,
+    events: EventBus,
+    /// 等待时被取消则不再点击
+    cancel: CancellationToken,
+    frame_source: Arc<dyn FrameSource>,
+    input: Arc<dyn InputBackend>,
 }
 
 impl Automation {
-    pub fn new(window_title: &str) -> Self {
+    pub fn new(
+        window_id: WindowId,
+        frame_source: Arc<dyn FrameSource>,
+        input: Arc<dyn InputBackend>,
+        events: EventBus,
+        cancel: CancellationToken,
+    ) -> Self {
         Self {
+            window_id,
             screenshot_mat: None,
             screenshot_pos: None,
             screenshot_factor: 1.0,
             window_region: None,
+            window: None,
             cache: HashMap::new(),
-            window_title: window_title.to_owned(),
+            dry_run: false,
+            restore_cursor: false,
+            mouse_outside: MouseOutsidePolicy::default(),
+            safe_point: SAFE_POINT,
+            waiting_for_mouse: false,
+            stats: Stats::default(),
+            recorder: FlightRecorder::new(window_id, RecorderConfig::default()),
+            humanizer: Humanizer::new(HumanizeConfig::default()),
+            governor: InputGovernor::new(GovernorConfig::default()),
+            retry: false,
+            events,
+            cancel,
+            frame_source,
+            input,
         }
     }
 
-    pub fn take_screenshot(&mut self, crop: Option<CropRatio>) -> SrPlotResult<()> {
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    pub fn stats_mut(&mut self) -> &mut Stats {
+        &mut self.stats
+    }
+
+    /// 等待 `duration`，被取消时提前返回 `false`
+    pub fn sleep(&self, duration: Duration) -> bool {
+        self.cancel.sleep(duration)
+    }
+
+    /// 最近一次截图缩小到 1080p 的比例，没有缩小时为 1
+    pub fn screenshot_factor(&self) -> f64 {
+        self.screenshot_factor
+    }
+
+    pub fn recorder(&self) -> &FlightRecorder {
+        &self.recorder
+    }
+
+    pub fn recorder_mut(&mut self) -> &mut FlightRecorder {
+        &mut self.recorder
+    }
+
+    /// 设置点击的随机化，会重新创建随机数生成器
+    pub fn set_humanize(&mut self, config: HumanizeConfig) {
+        self.humanizer = Humanizer::new(config);
+    }
+
+    /// 开启后只识别不点击
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    pub fn set_governor(&mut self, config: GovernorConfig) {
+        self.governor.set_config(config);
+    }
+
+    /// 开启后点击选项后把鼠标移回原来的位置
+    pub fn set_restore_cursor(&mut self, restore_cursor: bool) {
+        self.restore_cursor = restore_cursor;
+    }
+
+    /// 设置鼠标不在窗口内时的处理方式，`safe_point` 为移动鼠标时的目标位置占窗口大小的比例
+    pub fn set_mouse_outside(&mut self, policy: MouseOutsidePolicy, safe_point: (f32, f32)) {
+        self.mouse_outside = policy;
+        self.safe_point = safe_point;
+    }
+
+    /// 清空模板缓存，下次匹配时重新解码
+    pub fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
+
+    pub fn take_screenshot(
+        &mut self,
+        window: &GameWindow,
+        crop: Option<CropRatio>,
+    ) -> SrPlotResult<()> {
         let timer = Instant::now();
 
         let (screenshot, screenshot_pos, screenshot_factor, window_region) =
-            screenshot::take_screenshot(&self.window_title, crop)?;
+            screenshot::take_screenshot(self.frame_source.as_ref(), window, crop)?;
+        self.recorder.push(&screenshot, screenshot_pos);
+        self.window = Some(window.clone());
         self.screenshot_pos = Some(screenshot_pos);
         self.window_region = Some(window_region);
         self.screenshot_factor = screenshot_factor;
@@ -76,56 +210,185 @@ impl Automation {
                 ImreadModes::IMREAD_COLOR as i32,
             )?)
         };
+        self.stats.record_screenshot(timer.elapsed());
+        self.events.emit(PlotEvent::FrameCaptured {
+            window: self.window_id,
+            elapsed: timer.elapsed(),
+        });
 
         log::debug!(
-            "截图成功，耗时：{}ms, 截图区域：{:?}，缩放比例：{:.2}",
-            timer.elapsed().as_millis(),
-            screenshot_pos,
-            screenshot_factor
+            "{}",
+            tr!(
+                "automation.captured",
+                ms = timer.elapsed().as_millis(),
+                region = format!("{:?}", screenshot_pos),
+                factor = format!("{:.2}", screenshot_factor)
+            )
         );
         Ok(())
     }
 
     pub fn find_element(
         &mut self,
-        target: &(&'static str, Vec<u8>),
+        target: &ImageFile,
         threshold: f64,
         scale_range: Option<ScaleRange>,
     ) -> SrPlotResult<Option<Coordinate>> {
         log::debug!("scale_range: {:?}", scale_range);
-        let (target_name, target_data) = target;
-
-        let template = {
-            if !self.cache.contains_key(*target_name) {
-                let template = imdecode(&target_data.as_slice(), ImreadModes::IMREAD_COLOR as i32)?;
-                self.cache.insert(target_name.to_string(), template);
-            }
-            self.cache
-                .get(*target_name)
-                .ok_or(SrPlotError::Unexcepted)?
-        };
+        let timer = Instant::now();
+        let (target_name, _) = target;
 
+        self.load_template(target)?;
+        let template = self
+            .cache
+            .get(target_name.as_str())
+            .ok_or_else(|| SrPlotError::TemplateNotLoaded(target_name.clone()))?;
         let screenshot = self
             .screenshot_mat
             .as_ref()
-            .ok_or(SrPlotError::Unexcepted)?;
+            .ok_or(SrPlotError::NoFrameYet)?;
 
-        let (match_val, match_loc) =
+        let (match_val, match_loc, _) =
             scale_and_match_template(screenshot, template, threshold, scale_range)?;
+        let location = self.calculate_positions(template, match_loc)?;
+        self.stats.record_matching(timer.elapsed());
+        self.emit_matched(target_name, match_val, location, timer.elapsed());
 
-        log::debug!("目标图片：{}, 相似度：{:.2}", target_name, match_val);
+        log::debug!(
+            template = target_name.as_str(),
+            score = match_val;
+            "{}",
+            tr!("automation.score", template = target_name, score = format!("{:.2}", match_val))
+        );
 
-        if match_val.is_finite() && match_val >= threshold {
+        let coordinate = if match_val.is_finite() && match_val >= threshold {
             log::debug!(
-                "目标图片匹配成功，位置：({:.2}, {:.2})",
-                match_loc.x,
-                match_loc.y
+                "{}",
+                tr!("automation.matched", x = match_loc.x, y = match_loc.y)
             );
-            let (top_left, bottom_right) = self.calculate_positions(template, match_loc)?;
-            Ok(Some((top_left, bottom_right)))
+            Some(location)
         } else {
-            Ok(None)
+            None
+        };
+        self.recorder.add_detection(Detection {
+            template: target_name.clone(),
+            score: match_val,
+            coordinate,
+        });
+        Ok(coordinate)
+    }
+
+    /// 找出所有相似度不低于 `threshold` 的目标，按从上到下的顺序返回
+    pub fn find_elements(
+        &mut self,
+        target: &ImageFile,
+        threshold: f64,
+        scale_range: Option<ScaleRange>,
+    ) -> SrPlotResult<Vec<Candidate>> {
+        let timer = Instant::now();
+        let (target_name, _) = target;
+
+        self.load_template(target)?;
+        let template = self
+            .cache
+            .get(target_name.as_str())
+            .ok_or_else(|| SrPlotError::TemplateNotLoaded(target_name.clone()))?;
+        let screenshot = self
+            .screenshot_mat
+            .as_ref()
+            .ok_or(SrPlotError::NoFrameYet)?;
+
+        let (match_val, match_loc, scale_factor) =
+            scale_and_match_template(screenshot, template, threshold, scale_range)?;
+        // 在最佳缩放比例下重新匹配，找出所有候选位置
+        let template = resize_template(template, scale_factor)?;
+        let location = self.calculate_positions(&template, match_loc)?;
+        if !match_val.is_finite() || match_val < threshold {
+            self.stats.record_matching(timer.elapsed());
+            self.emit_matched(target_name, match_val, location, timer.elapsed());
+            log::debug!(
+                template = target_name.as_str(),
+                score = match_val;
+                "{}",
+                tr!("automation.score", template = target_name, score = format!("{:.2}", match_val))
+            );
+            self.recorder.add_detection(Detection {
+                template: target_name.clone(),
+                score: match_val,
+                coordinate: None,
+            });
+            return Ok(Vec::new());
+        }
+
+        let result = match_template(screenshot, &template, TemplateMatchModes::TM_CCOEFF_NORMED)?;
+        let locations = find_locations(&result, threshold, template.size()?.height)?;
+        self.stats.record_matching(timer.elapsed());
+        self.emit_matched(target_name, match_val, location, timer.elapsed());
+
+        log::debug!(
+            template = target_name.as_str(),
+            count = locations.len();
+            "{}",
+            tr!(
+                "automation.found",
+                template = target_name,
+                count = locations.len(),
+                scores = format!(
+                    "{:?}",
+                    locations
+                        .iter()
+                        .map(|(val, _)| format!("{:.2}", val))
+                        .collect::<Vec<_>>()
+                )
+            )
+        );
+
+        let candidates = locations
+            .into_iter()
+            .map(|(score, loc)| {
+                Ok(Candidate {
+                    coordinate: self.calculate_positions(&template, loc)?,
+                    score,
+                })
+            })
+            .collect::<SrPlotResult<Vec<_>>>()?;
+        for candidate in &candidates {
+            self.recorder.add_detection(Detection {
+                template: target_name.clone(),
+                score: candidate.score,
+                coordinate: Some(candidate.coordinate),
+            });
+        }
+        Ok(candidates)
+    }
+
+    /// 发布匹配完成的事件
+    fn emit_matched(&self, template: &str, score: f64, location: Coordinate, elapsed: Duration) {
+        self.events.emit(PlotEvent::TemplateMatched {
+            window: self.window_id,
+            template: template.to_string(),
+            score,
+            location,
+            elapsed,
+        });
+    }
+
+    fn load_template(&mut self, target: &ImageFile) -> SrPlotResult<()> {
+        let (target_name, target_data) = target;
+        if !self.cache.contains_key(target_name) {
+            let decode_failed = |message: String| SrPlotError::TemplateDecodeFailed {
+                name: target_name.clone(),
+                message,
+            };
+            let template = imdecode(&target_data.as_slice(), ImreadModes::IMREAD_COLOR as i32)
+                .map_err(|e| decode_failed(e.to_string()))?;
+            // 数据不是有效的图片时 imdecode 不会报错，而是返回空的 Mat
+            if template.empty() {
+                return Err(decode_failed(tr!("error.invalid_image")));
+            }
+            self.cache.insert(target_name.clone(), template);
         }
+        Ok(())
     }
 
     fn calculate_positions(&self, template: &Mat, max_loc: Point) -> SrPlotResult<Coordinate> {
@@ -136,7 +399,7 @@ impl Automation {
             x: sspos_x,
             y: sspos_y,
             ..
-        } = self.screenshot_pos.ok_or(SrPlotError::Unexcepted)?;
+        } = self.screenshot_pos.ok_or(SrPlotError::NoFrameYet)?;
         let top_left = (
             (max_loc.x as f64 / scale_factor) as u32 + sspos_x,
             (max_loc.y as f64 / scale_factor) as u32 + sspos_y,
@@ -149,30 +412,189 @@ impl Automation {
         Ok((top_left, bottom_right))
     }
 
-    pub fn click(&self) -> SrPlotResult<()> {
-        let (mouse_x, mouse_y) = Input::position();
-        log::debug!("鼠标位置：({}, {})", mouse_x, mouse_y);
+    /// 在鼠标当前位置点击，返回是否真的发送了点击
+    pub fn click(&mut self) -> SrPlotResult<bool> {
+        if self.dry_run {
+            log::info!("{}", tr!("automation.dry_run_click"));
+            self.recorder.set_action(Action::Skipped);
+            return Ok(false);
+        }
+        if !self.permit()? {
+            return Ok(false);
+        }
+        let clicked = self.send_click()?;
+        if clicked {
+            self.governor.record(Instant::now(), false);
+        }
+        Ok(clicked)
+    }
+
+    /// 点击 `coordinate` 范围内的选项，返回是否真的发送了点击
+    pub fn click_with_coordinate(&mut self, coordinate: Coordinate) -> SrPlotResult<bool> {
+        let (x, y) = self.humanizer.click_point(coordinate);
+
+        if self.dry_run {
+            log::info!(x = x, y = y; "{}", tr!("automation.dry_run_choice", x = x, y = y));
+            self.recorder.set_action(Action::Skipped);
+            return Ok(false);
+        }
+        if !self.permit()? {
+            return Ok(false);
+        }
+        let origin = self.input.position();
+        let result = match self.move_along_path(origin, (x, y)) {
+            Ok(true) => self.send_click(),
+            moved => moved,
+        };
+        // 先记录点击，恢复鼠标位置失败时冷却时间也照常生效
+        if matches!(result, Ok(true)) {
+            self.governor.record(Instant::now(), true);
+        }
+        // 被取消或移动失败时鼠标停在半路，同样要移回原位
+        if self.restore_cursor {
+            if let Err(e) = self.input.move_mouse(origin.0, origin.1) {
+                log::warn!("{}", tr!("automation.restore_failed", message = e));
+            }
+        }
+        result
+    }
+
+    /// 沿模拟的轨迹把鼠标从 `from` 移动到 `to`，被取消时返回 `false`
+    fn move_along_path(&mut self, from: (u32, u32), to: (u32, u32)) -> SrPlotResult<bool> {
+        for ((x, y), interval) in self.humanizer.mouse_path(from, to) {
+            self.input.move_mouse(x, y)?;
+            if !self.sleep(interval) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// 按下 `key` 选择当前高亮的选项，不移动鼠标，返回是否真的按下了
+    pub fn press_key(&mut self, key: char) -> SrPlotResult<bool> {
+        if self.dry_run {
+            log::info!("{}", tr!("automation.dry_run_key", key = key));
+            self.recorder.set_action(Action::Skipped);
+            return Ok(false);
+        }
+        if !self.permit()? {
+            return Ok(false);
+        }
+        let delay = self.humanizer.click_delay();
+        if !self.sleep(delay) {
+            return Ok(false);
+        }
+        self.input.press_key(key)?;
+        self.stats.record_click();
+        self.governor.record(Instant::now(), true);
+        self.events.emit(PlotEvent::KeyPressed {
+            window: self.window_id,
+            key,
+        });
+        Ok(true)
+    }
+
+    /// 重新截取最近一次截图的整个窗口，计算画面签名
+    pub fn frame_signature(&self) -> SrPlotResult<u64> {
+        let window = self.window.as_ref().ok_or(SrPlotError::NoFrameYet)?;
+        Ok(frame_signature(&self.frame_source.capture(window)?))
+    }
+
+    /// 标记之后的点击是否为重试，重试时不受冷却时间和相同画面的限制
+    pub fn set_retry(&mut self, retry: bool) {
+        self.retry = retry;
+    }
+
+    /// 由输入调节器决定现在能否点击
+    fn permit(&mut self) -> SrPlotResult<bool> {
+        let signature = if self.governor.config().skip_same_frame && !self.retry {
+            Some(self.frame_signature()?)
+        } else {
+            None
+        };
+        match self.governor.check(Instant::now(), signature, self.retry) {
+            Ok(()) => Ok(true),
+            Err(reason) => {
+                log::debug!("{}", tr!("automation.throttled", reason = reason));
+                Ok(false)
+            }
+        }
+    }
+
+    /// 检查鼠标位置后发送点击，鼠标不在窗口内且选择等待时返回 `false`
+    fn send_click(&mut self) -> SrPlotResult<bool> {
+        let (mut mouse_x, mut mouse_y) = self.input.position();
+        log::debug!(
+            "{}",
+            tr!("automation.mouse_position", x = mouse_x, y = mouse_y)
+        );
         let Region {
             x,
             y,
             width,
             height,
-        } = self.window_region.ok_or(SrPlotError::Unexcepted)?;
-        log::debug!("窗口位置：({}, {}, {}, {})", x, y, width, height);
-        if x <= mouse_x && mouse_x <= x + width && y <= mouse_y && mouse_y <= y + height {
-            Input::click()
-        } else {
-            Err(SrPlotError::User("鼠标不在游戏窗口内！".to_string()))
-        }
-    }
+        } = self.window_region.ok_or(SrPlotError::NoFrameYet)?;
+        log::debug!(
+            "{}",
+            tr!(
+                "automation.window_region",
+                x = x,
+                y = y,
+                width = width,
+                height = height
+            )
+        );
 
-    pub fn click_with_coordinate(&self, coordinate: Coordinate) -> SrPlotResult<()> {
-        let ((left, top), (right, bottom)) = coordinate;
-        let x = (left + right) / 2;
-        let y = (top + bottom) / 2;
+        let inside = x <= mouse_x && mouse_x <= x + width && y <= mouse_y && mouse_y <= y + height;
+        if !inside {
+            match self.mouse_outside {
+                MouseOutsidePolicy::Error => {
+                    return Err(SrPlotError::MouseOutside {
+                        x: mouse_x,
+                        y: mouse_y,
+                        window: Region::new(x, y, width, height),
+                    })
+                }
+                MouseOutsidePolicy::Wait => {
+                    if !self.waiting_for_mouse {
+                        self.waiting_for_mouse = true;
+                        log::warn!("{}", tr!("automation.mouse_wait"));
+                    }
+                    return Ok(false);
+                }
+                MouseOutsidePolicy::Move => {
+                    let (ratio_x, ratio_y) = self.safe_point;
+                    mouse_x = x + (width as f32 * ratio_x.clamp(0.0, 1.0)) as u32;
+                    mouse_y = y + (height as f32 * ratio_y.clamp(0.0, 1.0)) as u32;
+                    log::debug!(
+                        "{}",
+                        tr!("automation.mouse_moved", x = mouse_x, y = mouse_y)
+                    );
+                    self.input.move_mouse(mouse_x, mouse_y)?;
+                }
+            }
+        } else if self.waiting_for_mouse {
+            self.waiting_for_mouse = false;
+            log::info!("{}", tr!("automation.mouse_back"));
+        }
 
-        Input::move_mouse(x, y)?;
-        self.click()
+        let delay = self.humanizer.click_delay();
+        if !self.sleep(delay) {
+            return Ok(false);
+        }
+        self.input.click_for(self.humanizer.press_duration())?;
+        self.stats.record_click();
+        log::debug!(x = mouse_x, y = mouse_y; "{}", tr!("automation.clicked", x = mouse_x, y = mouse_y));
+        self.recorder.set_action(Action::Click {
+            x: mouse_x,
+            y: mouse_y,
+        });
+        self.events.emit(PlotEvent::ClickSent {
+            window: self.window_id,
+            x: mouse_x,
+            y: mouse_y,
+        });
+        Ok(true)
     }
 }
 
@@ -181,7 +603,7 @@ fn scale_and_match_template(
     template: &Mat,
     threshold: f64,
     scale_range: Option<(f64, f64)>,
-) -> SrPlotResult<(f64, Point)> {
+) -> SrPlotResult<(f64, Point, f64)> {
     log::debug!(
         "screenshot size: {:?}, template size: {:?}",
         screenshot.size(),
@@ -190,9 +612,9 @@ fn scale_and_match_template(
 
     let result = match_template(screenshot, template, TemplateMatchModes::TM_CCOEFF_NORMED)?;
 
-    let (max_val, max_loc) = match scale_range {
+    let (max_val, max_loc, max_scale) = match scale_range {
         Some((scale_start, scale_end)) => {
-            let (mut max_val, mut max_loc) = (0f64, Point::default());
+            let (mut max_val, mut max_loc, mut max_scale) = (0f64, Point::default(), 1.0);
             let mut scale_factor = scale_start;
             while scale_factor < scale_end + 0.0001 && max_val < threshold {
                 let scaled_template = resize_template(template, scale_factor)?;
@@ -206,15 +628,19 @@ fn scale_and_match_template(
                 if local_max_val > max_val {
                     max_val = local_max_val;
                     max_loc = local_max_loc;
+                    max_scale = scale_factor;
                 }
                 scale_factor += 0.05;
             }
-            (max_val, max_loc)
+            (max_val, max_loc, max_scale)
+        }
+        None => {
+            let (max_val, max_loc) = find_max_location(&result)?;
+            (max_val, max_loc, 1.0)
         }
-        None => find_max_location(&result)?,
     };
 
-    Ok((max_val, max_loc))
+    Ok((max_val, max_loc, max_scale))
 }
 
 fn resize_template(template: &Mat, scale_factor: f64) -> SrPlotResult<Mat> {
@@ -244,9 +670,40 @@ fn find_max_location(src: &Mat) -> SrPlotResult<(f64, Point)> {
     Ok((max_val, max_loc))
 }
 
+/// 找出 `result` 中所有不低于 `threshold` 的位置，
+/// 垂直距离小于 `min_distance` 的位置只保留相似度最高的一个
+fn find_locations(
+    result: &Mat,
+    threshold: f64,
+    min_distance: i32,
+) -> SrPlotResult<Vec<(f64, Point)>> {
+    let cols = result.cols();
+    let mut candidates: Vec<(f64, Point)> = result
+        .data_typed::<f32>()?
+        .iter()
+        .enumerate()
+        .filter(|(_, val)| val.is_finite() && **val as f64 >= threshold)
+        .map(|(i, val)| (*val as f64, Point::new(i as i32 % cols, i as i32 / cols)))
+        .collect();
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut locations: Vec<(f64, Point)> = Vec::new();
+    for (val, loc) in candidates {
+        if locations
+            .iter()
+            .all(|(_, other)| (other.y - loc.y).abs() >= min_distance)
+        {
+            locations.push((val, loc));
+        }
+    }
+    locations.sort_by_key(|(_, loc)| loc.y);
+    Ok(locations)
+}
+
 fn match_template(image: &Mat, templ: &Mat, method: TemplateMatchModes) -> SrPlotResult<Mat> {
     let mut result = Mat::default();
-    match_template_def(image, templ, &mut result, method as i32).unwrap();
+    match_template_def(image, templ, &mut result, method as i32)
+        .map_err(|e| SrPlotError::image_processing("matchTemplate", e))?;
     Ok(result)
 }
 
@@ -0,0 +1,172 @@
+use std::{fs, path::Path, sync::Arc, time::Duration};
+
+use crate::{
+    config::Config,
+    error::SrPlotResult,
+    input::{EnigoInput, InputBackend},
+    plot::{CropRatio, ImageFile, Plot},
+    screenshot::{DesktopCapture, FrameSource},
+    utils::{TitlePattern, WindowMatcher},
+};
+
+/// 读取图片文件作为模板，模板名称为文件名
+pub fn load_image(path: impl AsRef<Path>) -> SrPlotResult<ImageFile> {
+    let path = path.as_ref();
+    let name = path.file_name().map_or_else(
+        || path.display().to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    );
+    Ok((name, fs::read(path)?))
+}
+
+/// 创建 [`Plot`]，未设置的部分使用默认值：
+///
+/// - 窗口属于 `StarRail.exe` 进程，且标题为任一语言客户端的标题
+/// - 内置的模板图片
+/// - [`Config::default`]
+/// - 通过 [`DesktopCapture`] 截图，通过 [`EnigoInput`] 模拟鼠标
+pub struct PlotBuilder {
+    pub(crate) select_img: ImageFile,
+    pub(crate) start_img: Vec<ImageFile>,
+    pub(crate) config: Config,
+    pub(crate) frame_source: Arc<dyn FrameSource>,
+    pub(crate) input: Arc<dyn InputBackend>,
+}
+
+impl Default for PlotBuilder {
+    fn default() -> Self {
+        let image_files = vec![
+            (
+                "start.png".to_string(),
+                include_bytes!("../assets/start.png").to_vec(),
+            ),
+            (
+                "start_ps5.png".to_string(),
+                include_bytes!("../assets/start_ps5.png").to_vec(),
+            ),
+            (
+                "start_xbox.png".to_string(),
+                include_bytes!("../assets/start_xbox.png").to_vec(),
+            ),
+        ];
+
+        let select_image = (
+            "select.png".to_string(),
+            include_bytes!("../assets/select.png").to_vec(),
+        );
+
+        Self {
+            select_img: select_image,
+            start_img: image_files,
+            config: Config::default(),
+            frame_source: Arc::new(DesktopCapture::default()),
+            input: Arc::new(EnigoInput),
+        }
+    }
+}
+
+impl PlotBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 窗口标题需要包含 `title`
+    ///
+    /// 只替换标题规则，默认仍然要求窗口属于 `StarRail.exe`，匹配其他程序的窗口时需要同时调用
+    /// [`window_process`](Self::window_process)
+    pub fn window_title(mut self, title: impl Into<String>) -> Self {
+        self.config.window.titles = vec![TitlePattern::Contains(title.into())];
+        self
+    }
+
+    /// 窗口标题满足其中任意一条即可，排在前面的优先级更高
+    pub fn window_titles(mut self, titles: Vec<TitlePattern>) -> Self {
+        self.config.window.titles = titles;
+        self
+    }
+
+    /// 窗口需要属于可执行文件名为 `process` 的进程，`None` 表示不检查进程
+    pub fn window_process(mut self, process: Option<String>) -> Self {
+        self.config.window.process = process;
+        self
+    }
+
+    pub fn window_matcher(mut self, matcher: WindowMatcher) -> Self {
+        self.config.window = matcher;
+        self
+    }
+
+    /// 同时处理所有匹配的窗口
+    pub fn multi_window(mut self, multi_window: bool) -> Self {
+        self.config.multi_window = multi_window;
+        self
+    }
+
+    /// 选项图标的模板
+    pub fn select_image(mut self, image: ImageFile) -> Self {
+        self.select_img = image;
+        self
+    }
+
+    /// 对话开始图标的模板，任意一个匹配成功即视为进入对话
+    pub fn start_images(mut self, images: Vec<ImageFile>) -> Self {
+        self.start_img = images;
+        self
+    }
+
+    pub fn add_start_image(mut self, image: ImageFile) -> Self {
+        self.start_img.push(image);
+        self
+    }
+
+    /// 替换全部配置，之后调用的其他设置仍然生效
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn start_crop(mut self, crop: CropRatio) -> Self {
+        self.config.start_crop = crop;
+        self
+    }
+
+    pub fn select_crop(mut self, crop: CropRatio) -> Self {
+        self.config.select_crop = crop;
+        self
+    }
+
+    pub fn start_threshold(mut self, threshold: f64) -> Self {
+        self.config.start_threshold = threshold;
+        self
+    }
+
+    pub fn select_threshold(mut self, threshold: f64) -> Self {
+        self.config.select_threshold = threshold;
+        self
+    }
+
+    /// 每次检测的间隔
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.config.interval = interval.as_millis() as u64;
+        self
+    }
+
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.config.dry_run = dry_run;
+        self
+    }
+
+    pub fn input(mut self, input: impl InputBackend + 'static) -> Self {
+        self.input = Arc::new(input);
+        self
+    }
+
+    pub fn frame_source(mut self, frame_source: impl FrameSource + 'static) -> Self {
+        self.frame_source = Arc::new(frame_source);
+        self
+    }
+
+    pub fn build(self) -> Plot {
+        Plot::from_builder(self)
+    }
+}
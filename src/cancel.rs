@@ -0,0 +1,41 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// 跨线程共享的取消标记，`Plot` 在每次检测之间和等待期间检查它
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// 分段休眠，被取消时提前返回 `false`
+    pub fn sleep(&self, duration: Duration) -> bool {
+        const STEP: Duration = Duration::from_millis(20);
+
+        let deadline = Instant::now() + duration;
+        while !self.is_cancelled() {
+            let now = Instant::now();
+            if now >= deadline {
+                return true;
+            }
+            thread::sleep(STEP.min(deadline - now));
+        }
+        false
+    }
+}
@@ -0,0 +1,137 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    automation::{MouseOutsidePolicy, SAFE_POINT},
+    error::{SrPlotError, SrPlotResult},
+    governor::GovernorConfig,
+    hook::HookConfig,
+    humanize::HumanizeConfig,
+    i18n::Lang,
+    logging::LogConfig,
+    metrics::MetricsConfig,
+    plot::{CropRatio, SELECT_IMAGE_CROP, START_IMAGE_CROP},
+    recorder::RecorderConfig,
+    utils::WindowMatcher,
+    webhook::WebhookConfig,
+};
+
+pub const CONFIG_PATH: &str = "config.toml";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// 每次检测的间隔（毫秒）
+    pub interval: u64,
+    /// 只识别不点击
+    pub dry_run: bool,
+    /// 如何找到游戏窗口
+    pub window: WindowMatcher,
+    /// 同时处理所有匹配的窗口，否则只处理优先级最高的一个
+    pub multi_window: bool,
+    /// 退出时把统计数据以 JSON 格式写入该目录，不设置则不写入
+    pub stats_dir: Option<PathBuf>,
+    /// 对话开始图标的截图区域
+    pub start_crop: CropRatio,
+    /// 选项图标的截图区域
+    pub select_crop: CropRatio,
+    /// 对话开始图标的相似度阈值
+    pub start_threshold: f64,
+    /// 选项图标的相似度阈值
+    pub select_threshold: f64,
+    /// 点击时鼠标不在游戏窗口内的处理方式
+    pub mouse_outside: MouseOutsidePolicy,
+    /// `mouse_outside = "move"` 时把鼠标移动到的位置，分别为 x、y 占窗口大小的比例
+    pub safe_point: (f32, f32),
+    /// 点击选项后把鼠标移回原来的位置
+    pub restore_cursor: bool,
+    /// 设置后，窗口在前台时按下该键选择默认高亮的选项，不移动鼠标
+    pub choice_key: Option<char>,
+    /// 保留最近的截图，用于复现点错选项或出错的情况
+    pub recorder: RecorderConfig,
+    /// 随机化点击的时长、位置和鼠标移动
+    pub humanize: HumanizeConfig,
+    /// 限制点击频率
+    pub governor: GovernorConfig,
+    /// 检查点击是否生效
+    pub verify: VerifyConfig,
+    /// 界面语言，不设置时使用系统语言
+    pub lang: Option<Lang>,
+    /// 写入日志文件
+    pub log: LogConfig,
+    /// 通过 HTTP 提供 Prometheus 格式的运行指标
+    pub metrics: MetricsConfig,
+    /// 发生特定事件时发送 HTTP 请求
+    pub webhooks: Vec<WebhookConfig>,
+    /// 发生特定事件时运行外部命令
+    pub hooks: Vec<HookConfig>,
+}
+
+/// 点击后检查是否生效，对应配置文件中的 `[verify]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct VerifyConfig {
+    pub enabled: bool,
+    /// 没有生效时最多重试的次数
+    pub retries: u32,
+    /// 点击后等待多久再检查（毫秒），每次重试后翻倍
+    pub backoff_ms: u64,
+}
+
+impl Default for VerifyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            retries: 2,
+            backoff_ms: 200,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            interval: 500,
+            dry_run: false,
+            window: WindowMatcher::default(),
+            multi_window: false,
+            stats_dir: None,
+            start_crop: START_IMAGE_CROP,
+            select_crop: SELECT_IMAGE_CROP,
+            start_threshold: 0.9,
+            // 遇到过 0.89 匹配不上，所以降低 threshold
+            select_threshold: 0.88,
+            mouse_outside: MouseOutsidePolicy::default(),
+            safe_point: SAFE_POINT,
+            restore_cursor: false,
+            choice_key: None,
+            recorder: RecorderConfig::default(),
+            humanize: HumanizeConfig::default(),
+            governor: GovernorConfig::default(),
+            verify: VerifyConfig::default(),
+            lang: None,
+            log: LogConfig::default(),
+            metrics: MetricsConfig::default(),
+            webhooks: Vec::new(),
+            hooks: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// 从文件读取配置，文件不存在时使用默认配置
+    pub fn load(path: impl AsRef<Path>) -> SrPlotResult<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)
+            .map_err(|e| SrPlotError::Config(format!("{}: {}", path.display(), e)))?;
+        toml::from_str(&content)
+            .map_err(|e| SrPlotError::Config(format!("{}: {}", path.display(), e)))
+    }
+}
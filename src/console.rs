@@ -0,0 +1,71 @@
+use std::{
+    io::{self, BufRead},
+    str::FromStr,
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use crate::{i18n, tr};
+
+/// 可用命令的说明
+pub fn help() -> &'static str {
+    i18n::text("help")
+}
+
+/// 运行期间可以发送给 `Plot` 的命令
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Pause,
+    Resume,
+    Status,
+    Stats,
+    DryRun(bool),
+    /// 导出飞行记录仪中的截图
+    Dump,
+    Reload,
+    Quit,
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        match words.as_slice() {
+            ["pause"] => Ok(Command::Pause),
+            ["resume"] => Ok(Command::Resume),
+            ["status"] => Ok(Command::Status),
+            ["stats"] => Ok(Command::Stats),
+            ["dry-run", "on"] => Ok(Command::DryRun(true)),
+            ["dry-run", "off"] => Ok(Command::DryRun(false)),
+            ["dump"] => Ok(Command::Dump),
+            ["reload"] => Ok(Command::Reload),
+            ["quit"] | ["exit"] => Ok(Command::Quit),
+            _ => Err(tr!("unknown_command", command = s.trim(), help = help())),
+        }
+    }
+}
+
+/// 在后台线程中读取标准输入，把每一行解析为命令
+///
+/// 标准输入关闭（例如管道另一端退出）时线程结束，`Plot` 继续运行
+pub fn spawn() -> Receiver<Command> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if line.trim().is_empty() {
+                continue;
+            }
+            match line.parse::<Command>() {
+                Ok(command) => {
+                    if sender.send(command).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => log::warn!("{}", e),
+            }
+        }
+    });
+    receiver
+}
@@ -1,29 +1,216 @@
 // use std::sync::{MutexGuard, TryLockError};
 
-#[derive(Debug, thiserror::Error)]
+use std::{fmt, time::Duration};
+
+use crate::{automation::Region, tr};
+
+#[derive(Debug)]
 pub enum SrPlotError {
-    #[error("截图失败: {0}")]
-    Screenshot(String),
-    #[error("模拟鼠标或键盘输入发生错误：{0}")]
-    Input(#[from] enigo::InputError),
-    #[error("处理图片时发生错误：{0}")]
-    ImageProcessing(String),
-    #[error("{0}")]
+    WindowNotFound(String),
+    WindowMinimized(String),
+    CaptureFailed {
+        method: String,
+        message: String,
+    },
+    NoFrameYet,
+    TemplateDecodeFailed {
+        name: String,
+        message: String,
+    },
+    TemplateNotLoaded(String),
+    Input(enigo::InputError),
+    ImageProcessing {
+        operation: &'static str,
+        message: String,
+    },
+    Config(String),
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    TimeFormat(time::error::Format),
     User(String),
-    #[error("未知错误")]
-    Unexcepted,
+    Unresponsive(String),
+    InputUnavailable(String),
+    /// 点击时鼠标 `(x, y)` 不在游戏窗口 `window` 内
+    MouseOutside {
+        x: u32,
+        y: u32,
+        window: Region,
+    },
+    Panic(String),
 }
 
 pub type SrPlotResult<T> = Result<T, SrPlotError>;
 
+/// 发生错误后的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recovery {
+    /// 下一次检测时直接重试
+    Retry,
+    /// 暂停处理该窗口一段时间后再重试
+    Backoff(Duration),
+    /// 无法恢复，停止运行
+    Stop,
+}
+
+impl SrPlotError {
+    /// 处理图片时发生的错误，`operation` 说明正在进行的操作
+    pub fn image_processing(operation: &'static str, error: impl ToString) -> Self {
+        Self::ImageProcessing {
+            operation,
+            message: error.to_string(),
+        }
+    }
+
+    /// 错误类型的名称，用于统计
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::WindowNotFound(_) => "window_not_found",
+            Self::WindowMinimized(_) => "window_minimized",
+            Self::CaptureFailed { .. } => "capture_failed",
+            Self::NoFrameYet => "no_frame_yet",
+            Self::TemplateDecodeFailed { .. } => "template_decode_failed",
+            Self::TemplateNotLoaded(_) => "template_not_loaded",
+            Self::Input(_) => "input",
+            Self::ImageProcessing { .. } => "image_processing",
+            Self::Config(_) => "config",
+            Self::Io(_) => "io",
+            Self::Json(_) => "json",
+            Self::TimeFormat(_) => "time_format",
+            Self::User(_) => "user",
+            Self::Unresponsive(_) => "unresponsive",
+            Self::InputUnavailable(_) => "input_unavailable",
+            Self::MouseOutside { .. } => "mouse_outside",
+            Self::Panic(_) => "panic",
+        }
+    }
+
+    /// 发生该错误后应该如何处理
+    pub fn recovery(&self) -> Recovery {
+        match self {
+            // 窗口最小化时截图没有意义，游戏没有响应时频繁重试也无济于事
+            Self::WindowMinimized(_) => Recovery::Backoff(Duration::from_secs(2)),
+            Self::Unresponsive(_) => Recovery::Backoff(Duration::from_secs(5)),
+            Self::Input(_) => Recovery::Backoff(Duration::from_secs(1)),
+            Self::InputUnavailable(_) => Recovery::Backoff(Duration::from_secs(5)),
+            // 等待用户把鼠标移回窗口内，不需要每次检测都重试
+            Self::MouseOutside { .. } => Recovery::Backoff(Duration::from_secs(1)),
+            // 内置的模板图片损坏或配置有误，重试不会有不同的结果
+            Self::TemplateDecodeFailed { .. } | Self::TemplateNotLoaded(_) | Self::Config(_) => {
+                Recovery::Stop
+            }
+            Self::WindowNotFound(_)
+            | Self::CaptureFailed { .. }
+            | Self::NoFrameYet
+            | Self::ImageProcessing { .. }
+            | Self::Io(_)
+            | Self::Json(_)
+            | Self::TimeFormat(_)
+            | Self::User(_)
+            | Self::Panic(_) => Recovery::Retry,
+        }
+    }
+
+    /// 是否是暂时性的错误，稍后重试可能恢复
+    pub fn is_transient(&self) -> bool {
+        self.recovery() != Recovery::Stop
+    }
+
+    /// 是否是无法恢复的错误
+    pub fn is_fatal(&self) -> bool {
+        self.recovery() == Recovery::Stop
+    }
+}
+
+/// 错误信息使用当前的界面语言
+impl fmt::Display for SrPlotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            Self::WindowNotFound(title) => tr!("error.window_not_found", title = title),
+            Self::WindowMinimized(title) => tr!("error.window_minimized", title = title),
+            Self::CaptureFailed { method, message } => {
+                tr!("error.capture_failed", method = method, message = message)
+            }
+            Self::NoFrameYet => tr!("error.no_frame_yet"),
+            Self::TemplateDecodeFailed { name, message } => {
+                tr!(
+                    "error.template_decode_failed",
+                    name = name,
+                    message = message
+                )
+            }
+            Self::TemplateNotLoaded(name) => tr!("error.template_not_loaded", name = name),
+            Self::Input(e) => tr!("error.input", message = e),
+            Self::ImageProcessing { operation, message } => tr!(
+                "error.image_processing",
+                operation = operation,
+                message = message
+            ),
+            Self::Config(message) => tr!("error.config", message = message),
+            Self::Io(e) => tr!("error.io", message = e),
+            Self::Json(e) => tr!("error.json", message = e),
+            Self::TimeFormat(e) => tr!("error.time_format", message = e),
+            Self::User(message) => message.clone(),
+            Self::Unresponsive(message) => tr!("error.unresponsive", message = message),
+            Self::InputUnavailable(message) => tr!("error.input_unavailable", message = message),
+            Self::MouseOutside { x, y, window } => tr!(
+                "error.mouse_outside",
+                x = x,
+                y = y,
+                left = window.x,
+                top = window.y,
+                width = window.width,
+                height = window.height
+            ),
+            Self::Panic(message) => tr!("error.panic", message = message),
+        };
+        f.write_str(&message)
+    }
+}
+
+impl std::error::Error for SrPlotError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Input(e) => Some(e),
+            Self::Io(e) => Some(e),
+            Self::Json(e) => Some(e),
+            Self::TimeFormat(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<enigo::InputError> for SrPlotError {
+    fn from(value: enigo::InputError) -> Self {
+        Self::Input(value)
+    }
+}
+
+impl From<std::io::Error> for SrPlotError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
 impl From<opencv::Error> for SrPlotError {
     fn from(value: opencv::Error) -> Self {
-        Self::ImageProcessing(value.to_string())
+        Self::image_processing("OpenCV", value)
     }
 }
 
 impl From<image::ImageError> for SrPlotError {
     fn from(value: image::ImageError) -> Self {
-        Self::ImageProcessing(value.to_string())
+        Self::image_processing("image", value)
+    }
+}
+
+impl From<serde_json::Error> for SrPlotError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Json(value)
+    }
+}
+
+impl From<time::error::Format> for SrPlotError {
+    fn from(value: time::error::Format) -> Self {
+        Self::TimeFormat(value)
     }
 }
@@ -0,0 +1,115 @@
+use std::{
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex, PoisonError,
+    },
+    time::Duration,
+};
+
+pub use crate::automation::{Candidate, Coordinate};
+pub use crate::plot::GameStatus;
+
+/// 游戏窗口的标识，与 [`GameWindow::id`](crate::screenshot::GameWindow) 相同
+pub type WindowId = isize;
+
+/// `Plot` 运行过程中发布的事件
+///
+/// `window` 为事件所属的游戏窗口，`None` 表示所有窗口的整体状态
+#[derive(Debug, Clone)]
+pub enum PlotEvent {
+    /// 游戏窗口状态改变
+    StatusChanged {
+        window: Option<WindowId>,
+        from: GameStatus,
+        to: GameStatus,
+    },
+    /// 进入对话
+    DialogueStarted { window: WindowId },
+    /// 对话结束（或不再处理该窗口）
+    DialogueEnded { window: WindowId },
+    /// 识别到新的选项（同一组选项只发布一次），`candidates` 按从上到下排列，`chosen` 为将要点击的选项序号
+    ChoiceDetected {
+        window: WindowId,
+        candidates: Vec<Candidate>,
+        chosen: usize,
+    },
+    /// 点击或按键选择了第 `index` 个选项
+    ChoiceSelected { window: WindowId, index: usize },
+    /// 发送了一次鼠标点击
+    ClickSent { window: WindowId, x: u32, y: u32 },
+    /// 按下 `key` 选择了选项，与点击一样计入点击次数
+    KeyPressed { window: WindowId, key: char },
+    /// 完成一次截图，`elapsed` 包含截图和转换格式的耗时
+    FrameCaptured { window: WindowId, elapsed: Duration },
+    /// 完成一次模板匹配，`score` 为最高的相似度，`location` 为相似度最高的位置（不论是否超过阈值）
+    TemplateMatched {
+        window: WindowId,
+        template: String,
+        score: f64,
+        location: Coordinate,
+        elapsed: Duration,
+    },
+    /// 完成一次检测（处理所有窗口）
+    TickCompleted { elapsed: Duration },
+    /// 同一个错误在一段时间内又重复出现了 `count` 次
+    ErrorRepeated {
+        window: WindowId,
+        kind: &'static str,
+        message: String,
+        count: u32,
+    },
+    /// `Plot::run` 即将返回
+    Stopped,
+    /// 一次检测中发生了错误，`logged` 为是否输出到了日志（短时间内重复的错误只计数，不输出）
+    Error {
+        window: Option<WindowId>,
+        kind: &'static str,
+        message: String,
+        logged: bool,
+    },
+}
+
+enum Subscriber {
+    Channel(Sender<PlotEvent>),
+    Callback(Box<dyn FnMut(&PlotEvent) + Send>),
+}
+
+/// 事件的订阅列表，克隆后共享同一份订阅
+#[derive(Clone, Default)]
+pub struct EventBus {
+    subscribers: Arc<Mutex<Vec<Subscriber>>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 通过 channel 订阅事件，`Receiver` 被丢弃后自动取消订阅
+    pub fn subscribe(&self) -> Receiver<PlotEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.lock().push(Subscriber::Channel(sender));
+        receiver
+    }
+
+    /// 注册回调函数，回调在 `Plot` 的线程中同步执行，不应长时间阻塞
+    pub fn on_event(&self, callback: impl FnMut(&PlotEvent) + Send + 'static) {
+        self.lock().push(Subscriber::Callback(Box::new(callback)));
+    }
+
+    pub(crate) fn emit(&self, event: PlotEvent) {
+        self.lock().retain_mut(|subscriber| match subscriber {
+            Subscriber::Channel(sender) => sender.send(event.clone()).is_ok(),
+            Subscriber::Callback(callback) => {
+                callback(&event);
+                true
+            }
+        });
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Subscriber>> {
+        self.subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -0,0 +1,247 @@
+use std::{
+    collections::{hash_map::DefaultHasher, VecDeque},
+    fmt,
+    hash::{Hash, Hasher},
+    time::{Duration, Instant},
+};
+
+use image::{imageops, RgbaImage};
+use serde::Deserialize;
+
+use crate::tr;
+
+/// 输入调节器的配置，对应配置文件中的 `[governor]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GovernorConfig {
+    /// 每秒最多点击的次数，为 0 时不限制
+    pub max_clicks_per_sec: u32,
+    /// 点击选项后多久内不再点击（毫秒），避免选项消失的动画中点击跳过下一句对话
+    pub choice_cooldown_ms: u64,
+    /// 画面与上一次点击时完全相同时不再点击
+    pub skip_same_frame: bool,
+}
+
+impl Default for GovernorConfig {
+    fn default() -> Self {
+        Self {
+            max_clicks_per_sec: 5,
+            choice_cooldown_ms: 1000,
+            skip_same_frame: false,
+        }
+    }
+}
+
+/// 跳过点击的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Throttle {
+    RateLimited,
+    Cooldown(Duration),
+    SameFrame,
+}
+
+impl fmt::Display for Throttle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Throttle::RateLimited => f.write_str(&tr!("governor.rate_limited")),
+            Throttle::Cooldown(remaining) => {
+                f.write_str(&tr!("governor.cooldown", ms = remaining.as_millis()))
+            }
+            Throttle::SameFrame => f.write_str(&tr!("governor.same_frame")),
+        }
+    }
+}
+
+/// 在调用 [`InputBackend`](crate::input::InputBackend) 之前统一决定能否点击
+///
+/// 时间由调用方传入，便于测试
+#[derive(Debug)]
+pub struct InputGovernor {
+    config: GovernorConfig,
+    /// 最近一秒内的点击时间
+    clicks: VecDeque<Instant>,
+    cooldown_until: Option<Instant>,
+    /// 上一次点击时的画面签名
+    last_signature: Option<u64>,
+    /// 最近一次检查通过时的画面签名，点击后成为 `last_signature`
+    pending_signature: Option<u64>,
+}
+
+impl InputGovernor {
+    pub fn new(config: GovernorConfig) -> Self {
+        Self {
+            config,
+            clicks: VecDeque::new(),
+            cooldown_until: None,
+            last_signature: None,
+            pending_signature: None,
+        }
+    }
+
+    pub fn config(&self) -> &GovernorConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: GovernorConfig) {
+        self.config = config;
+    }
+
+    /// 检查现在能否点击，`signature` 为当前画面的签名（不检查相同画面时为 `None`）
+    ///
+    /// `retry` 为 `true` 时表示上一次点击没有生效而重试，只检查点击频率
+    pub fn check(
+        &mut self,
+        now: Instant,
+        signature: Option<u64>,
+        retry: bool,
+    ) -> Result<(), Throttle> {
+        if let Some(until) = self.cooldown_until {
+            if now < until && !retry {
+                return Err(Throttle::Cooldown(until - now));
+            }
+        }
+
+        while self
+            .clicks
+            .front()
+            .is_some_and(|time| now.duration_since(*time) >= Duration::from_secs(1))
+        {
+            self.clicks.pop_front();
+        }
+        if self.config.max_clicks_per_sec > 0
+            && self.clicks.len() >= self.config.max_clicks_per_sec as usize
+        {
+            return Err(Throttle::RateLimited);
+        }
+
+        if self.config.skip_same_frame
+            && !retry
+            && signature.is_some()
+            && signature == self.last_signature
+        {
+            return Err(Throttle::SameFrame);
+        }
+
+        self.pending_signature = signature;
+        Ok(())
+    }
+
+    /// 记录一次已经发送的点击，`choice` 为点击的是否是选项
+    pub fn record(&mut self, now: Instant, choice: bool) {
+        self.clicks.push_back(now);
+        self.last_signature = self.pending_signature.take();
+        if choice {
+            self.cooldown_until = Some(now + Duration::from_millis(self.config.choice_cooldown_ms));
+        }
+    }
+}
+
+/// 画面的签名，缩小并降低色深后计算哈希，忽略细微的噪点
+pub fn frame_signature(image: &RgbaImage) -> u64 {
+    let thumbnail = imageops::thumbnail(image, 64, 36);
+    let mut hasher = DefaultHasher::new();
+    for pixel in thumbnail.pixels() {
+        let luma = (pixel[0] as u32 * 299 + pixel[1] as u32 * 587 + pixel[2] as u32 * 114) / 1000;
+        ((luma >> 4) as u8).hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn governor(max_clicks_per_sec: u32, skip_same_frame: bool) -> InputGovernor {
+        InputGovernor::new(GovernorConfig {
+            max_clicks_per_sec,
+            choice_cooldown_ms: 1000,
+            skip_same_frame,
+        })
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn limits_clicks_per_second() {
+        let mut governor = governor(2, false);
+        let start = Instant::now();
+        for millis in [0, 100] {
+            assert_eq!(governor.check(start + ms(millis), None, false), Ok(()));
+            governor.record(start + ms(millis), false);
+        }
+        assert_eq!(
+            governor.check(start + ms(500), None, false),
+            Err(Throttle::RateLimited)
+        );
+        // 重试也受频率限制
+        assert_eq!(
+            governor.check(start + ms(500), None, true),
+            Err(Throttle::RateLimited)
+        );
+        // 第一次点击滑出一秒的窗口后可以再点击
+        assert_eq!(governor.check(start + ms(1000), None, false), Ok(()));
+    }
+
+    #[test]
+    fn zero_means_unlimited() {
+        let mut governor = governor(0, false);
+        let start = Instant::now();
+        for _ in 0..100 {
+            assert_eq!(governor.check(start, None, false), Ok(()));
+            governor.record(start, false);
+        }
+    }
+
+    #[test]
+    fn cooldown_after_choice() {
+        let mut governor = governor(0, false);
+        let start = Instant::now();
+        governor.record(start, true);
+        assert_eq!(
+            governor.check(start + ms(400), None, false),
+            Err(Throttle::Cooldown(ms(600)))
+        );
+        assert_eq!(governor.check(start + ms(1000), None, false), Ok(()));
+
+        // 普通点击没有冷却时间
+        let mut governor = self::governor(0, false);
+        governor.record(start, false);
+        assert_eq!(governor.check(start + ms(1), None, false), Ok(()));
+    }
+
+    #[test]
+    fn retry_skips_cooldown_and_same_frame() {
+        let mut governor = governor(0, true);
+        let start = Instant::now();
+        assert_eq!(governor.check(start, Some(1), false), Ok(()));
+        governor.record(start, true);
+        assert_eq!(governor.check(start + ms(200), Some(1), true), Ok(()));
+    }
+
+    #[test]
+    fn skips_same_frame_as_last_click() {
+        let mut governor = governor(0, true);
+        let start = Instant::now();
+        assert_eq!(governor.check(start, Some(1), false), Ok(()));
+        governor.record(start, false);
+        assert_eq!(
+            governor.check(start + ms(100), Some(1), false),
+            Err(Throttle::SameFrame)
+        );
+        assert_eq!(governor.check(start + ms(100), Some(2), false), Ok(()));
+
+        // 检查通过但没有点击时不更新上一次点击的画面
+        assert_eq!(
+            governor.check(start + ms(200), Some(1), false),
+            Err(Throttle::SameFrame)
+        );
+
+        // 未开启时不检查
+        let mut governor = self::governor(0, false);
+        assert_eq!(governor.check(start, Some(1), false), Ok(()));
+        governor.record(start, false);
+        assert_eq!(governor.check(start + ms(100), Some(1), false), Ok(()));
+    }
+}
@@ -0,0 +1,442 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, BufRead, BufReader, Read, Write},
+    process::{Command, ExitStatus, Stdio},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::Level;
+use serde::Deserialize;
+use serde_json::Value;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{event::PlotEvent, plot::GameStatus, tr};
+
+/// 一个外部命令，对应配置文件中的 `[[hooks]]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct HookConfig {
+    /// 要运行的程序
+    pub command: String,
+    /// 程序的参数
+    pub args: Vec<String>,
+    /// 触发的事件，为空时所有事件都会运行
+    pub events: Vec<HookEvent>,
+    /// 除了环境变量外，再把事件的内容以 JSON 写入标准输入
+    pub stdin: bool,
+    /// 超过该时间（毫秒）仍未结束时结束进程
+    pub timeout_ms: u64,
+    /// 同时运行的数量上限，达到上限时忽略新的事件
+    pub max_concurrent: usize,
+}
+
+impl Default for HookConfig {
+    fn default() -> Self {
+        Self {
+            command: String::new(),
+            args: Vec::new(),
+            events: Vec::new(),
+            stdin: false,
+            timeout_ms: 10000,
+            max_concurrent: 1,
+        }
+    }
+}
+
+/// 可以触发外部命令的事件
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookEvent {
+    /// 找到游戏窗口
+    GameFound,
+    /// 游戏窗口消失
+    GameLost,
+    /// 进入对话
+    DialogueStarted,
+    /// 对话结束
+    DialogueEnded,
+    /// 选择了选项
+    ChoiceClicked,
+    /// 发生错误，30 秒内重复的同一个错误只触发一次
+    Error,
+}
+
+impl HookEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookEvent::GameFound => "game_found",
+            HookEvent::GameLost => "game_lost",
+            HookEvent::DialogueStarted => "dialogue_started",
+            HookEvent::DialogueEnded => "dialogue_ended",
+            HookEvent::ChoiceClicked => "choice_clicked",
+            HookEvent::Error => "error",
+        }
+    }
+}
+
+/// 一次要运行的事件，`vars` 为传给命令的值
+#[derive(Debug, Clone)]
+pub struct Invocation {
+    pub event: HookEvent,
+    pub vars: BTreeMap<&'static str, Value>,
+}
+
+impl Invocation {
+    /// 从 [`PlotEvent`] 生成，不需要运行命令的事件返回 `None`
+    pub fn from_event(event: &PlotEvent) -> Option<Self> {
+        let (kind, mut vars) = match event {
+            PlotEvent::StatusChanged {
+                window: Some(window),
+                from: GameStatus::Uninitialized | GameStatus::NotFound,
+                to: GameStatus::Active | GameStatus::Inactive,
+            } => (
+                HookEvent::GameFound,
+                BTreeMap::from([("window", Value::from(*window))]),
+            ),
+            PlotEvent::StatusChanged {
+                window: Some(window),
+                from: GameStatus::Active | GameStatus::Inactive,
+                to: GameStatus::NotFound,
+            } => (
+                HookEvent::GameLost,
+                BTreeMap::from([("window", Value::from(*window))]),
+            ),
+            PlotEvent::DialogueStarted { window } => (
+                HookEvent::DialogueStarted,
+                BTreeMap::from([("window", Value::from(*window))]),
+            ),
+            PlotEvent::DialogueEnded { window } => (
+                HookEvent::DialogueEnded,
+                BTreeMap::from([("window", Value::from(*window))]),
+            ),
+            PlotEvent::ChoiceSelected { window, index } => (
+                HookEvent::ChoiceClicked,
+                BTreeMap::from([
+                    ("window", Value::from(*window)),
+                    ("index", Value::from(*index)),
+                ]),
+            ),
+            // 重复的错误不输出日志，也不运行命令，避免每次检测都启动进程
+            PlotEvent::Error {
+                window,
+                kind,
+                message,
+                logged: true,
+            } => (
+                HookEvent::Error,
+                BTreeMap::from([
+                    ("window", window.map_or(Value::Null, Value::from)),
+                    ("kind", Value::from(*kind)),
+                    ("message", Value::from(message.as_str())),
+                ]),
+            ),
+            _ => return None,
+        };
+        let now = OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc());
+        vars.insert("event", Value::from(kind.name()));
+        vars.insert(
+            "time",
+            Value::from(now.format(&Rfc3339).unwrap_or_default()),
+        );
+        Some(Self { event: kind, vars })
+    }
+
+    /// 传给命令的环境变量，例如 `SR_PLOT_EVENT`、`SR_PLOT_WINDOW`，没有值的不设置
+    pub fn env(&self) -> Vec<(String, String)> {
+        self.vars
+            .iter()
+            .filter_map(|(name, value)| {
+                let value = match value {
+                    Value::Null => return None,
+                    Value::String(text) => text.clone(),
+                    value => value.to_string(),
+                };
+                Some((format!("SR_PLOT_{}", name.to_ascii_uppercase()), value))
+            })
+            .collect()
+    }
+
+    /// 写入标准输入的 JSON 对象
+    pub fn json(&self) -> String {
+        Value::Object(
+            self.vars
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect(),
+        )
+        .to_string()
+    }
+}
+
+/// 等待命令结束时检查的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+struct Hook {
+    config: Arc<HookConfig>,
+    running: Arc<AtomicUsize>,
+}
+
+/// 占用一个运行名额，丢弃时归还
+struct Slot(Arc<AtomicUsize>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl Hook {
+    fn acquire(&self) -> Option<Slot> {
+        self.running
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |running| {
+                (running < self.config.max_concurrent).then_some(running + 1)
+            })
+            .ok()
+            .map(|_| Slot(self.running.clone()))
+    }
+}
+
+/// 在后台线程中运行外部命令，不会阻塞调用方
+pub struct Hooks {
+    hooks: Vec<Hook>,
+}
+
+impl Hooks {
+    /// 没有配置任何命令时返回 `None`
+    pub fn new(configs: Vec<HookConfig>) -> Option<Self> {
+        if configs.is_empty() {
+            return None;
+        }
+        let hooks = configs
+            .into_iter()
+            .map(|config| Hook {
+                config: Arc::new(config),
+                running: Arc::new(AtomicUsize::new(0)),
+            })
+            .collect();
+        Some(Self { hooks })
+    }
+
+    /// 记录一个事件，为需要的命令各启动一个线程运行
+    pub fn observe(&self, event: &PlotEvent) {
+        let Some(invocation) = Invocation::from_event(event) else {
+            return;
+        };
+        for hook in &self.hooks {
+            let config = &hook.config;
+            if !config.events.is_empty() && !config.events.contains(&invocation.event) {
+                continue;
+            }
+            let Some(slot) = hook.acquire() else {
+                log::warn!(
+                    "{}",
+                    tr!(
+                        "hook.limit_reached",
+                        command = config.command,
+                        event = invocation.event.name()
+                    )
+                );
+                continue;
+            };
+            let config = config.clone();
+            let invocation = invocation.clone();
+            thread::spawn(move || {
+                let _slot = slot;
+                if let Err(e) = run(&config, &invocation) {
+                    log::warn!(
+                        "{}",
+                        tr!("hook.spawn_failed", command = config.command, message = e)
+                    );
+                }
+            });
+        }
+    }
+}
+
+/// 运行一次命令并等待结束，超时时结束进程并返回 `None`
+fn run(config: &HookConfig, invocation: &Invocation) -> io::Result<Option<ExitStatus>> {
+    let mut child = Command::new(&config.command)
+        .args(&config.args)
+        .envs(invocation.env())
+        .stdin(if config.stdin {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // 命令不读取标准输入就退出时写入会失败，不影响结果
+        let _ = stdin.write_all(invocation.json().as_bytes());
+    }
+    // 输出在单独的线程中读取，避免管道写满后命令卡住
+    if let Some(stdout) = child.stdout.take() {
+        capture(stdout, config.command.clone(), Level::Info);
+    }
+    if let Some(stderr) = child.stderr.take() {
+        capture(stderr, config.command.clone(), Level::Warn);
+    }
+
+    let deadline = Instant::now() + Duration::from_millis(config.timeout_ms);
+    loop {
+        if let Some(status) = child.try_wait()? {
+            if status.success() {
+                log::debug!(
+                    "{}",
+                    tr!(
+                        "hook.finished",
+                        command = config.command,
+                        event = invocation.event.name()
+                    )
+                );
+            } else {
+                log::warn!(
+                    "{}",
+                    tr!("hook.failed", command = config.command, status = status)
+                );
+            }
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            child.kill()?;
+            child.wait()?;
+            log::warn!(
+                "{}",
+                tr!(
+                    "hook.timed_out",
+                    command = config.command,
+                    ms = config.timeout_ms
+                )
+            );
+            return Ok(None);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// 把命令的每行输出写入日志
+fn capture(output: impl Read + Send + 'static, command: String, level: Level) {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(output);
+        let mut line = Vec::new();
+        while matches!(reader.read_until(b'\n', &mut line), Ok(len) if len > 0) {
+            let text = String::from_utf8_lossy(&line);
+            log::log!(level, hook = command.as_str(); "[{}] {}", command, text.trim_end());
+            line.clear();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dialogue_started() -> Invocation {
+        Invocation::from_event(&PlotEvent::DialogueStarted { window: 7 }).unwrap()
+    }
+
+    fn config(command: &str, args: &[&str]) -> HookConfig {
+        HookConfig {
+            command: command.to_string(),
+            args: args.iter().map(ToString::to_string).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn payload_contains_event_details() {
+        let invocation = dialogue_started();
+        let env: BTreeMap<String, String> = invocation.env().into_iter().collect();
+        assert_eq!(env["SR_PLOT_EVENT"], "dialogue_started");
+        assert_eq!(env["SR_PLOT_WINDOW"], "7");
+        assert!(env.contains_key("SR_PLOT_TIME"));
+
+        let json: Value = serde_json::from_str(&invocation.json()).unwrap();
+        assert_eq!(json["event"], "dialogue_started");
+        assert_eq!(json["window"], 7);
+    }
+
+    #[test]
+    fn error_without_window_skips_the_variable() {
+        let invocation = Invocation::from_event(&PlotEvent::Error {
+            window: None,
+            kind: "panic",
+            message: "boom".to_string(),
+            logged: true,
+        })
+        .unwrap();
+        let env: BTreeMap<String, String> = invocation.env().into_iter().collect();
+        assert!(!env.contains_key("SR_PLOT_WINDOW"));
+        assert_eq!(env["SR_PLOT_KIND"], "panic");
+        assert_eq!(env["SR_PLOT_MESSAGE"], "boom");
+
+        let throttled = PlotEvent::Error {
+            window: Some(1),
+            kind: "panic",
+            message: "boom".to_string(),
+            logged: false,
+        };
+        assert!(Invocation::from_event(&throttled).is_none());
+    }
+
+    #[test]
+    fn command_receives_env() {
+        let config = config(
+            "cmd",
+            &[
+                "/C",
+                "if %SR_PLOT_EVENT%_%SR_PLOT_WINDOW%==dialogue_started_7 (exit 0) else (exit 1)",
+            ],
+        );
+        let status = run(&config, &dialogue_started()).unwrap().unwrap();
+        assert!(status.success());
+    }
+
+    #[test]
+    fn command_receives_json_on_stdin() {
+        let mut config = config("findstr", &["dialogue_started"]);
+        config.stdin = true;
+        let status = run(&config, &dialogue_started()).unwrap().unwrap();
+        assert!(status.success());
+
+        config.stdin = false;
+        let status = run(&config, &dialogue_started()).unwrap().unwrap();
+        assert!(!status.success());
+    }
+
+    #[test]
+    fn kills_command_after_timeout() {
+        let mut config = config("ping", &["-n", "30", "127.0.0.1"]);
+        config.timeout_ms = 200;
+        let start = Instant::now();
+        assert!(run(&config, &dialogue_started()).unwrap().is_none());
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn ignores_events_over_the_concurrency_limit() {
+        let mut config = config("ping", &["-n", "3", "127.0.0.1"]);
+        config.max_concurrent = 1;
+        let hooks = Hooks::new(vec![config]).unwrap();
+        let running = &hooks.hooks[0].running;
+
+        let event = PlotEvent::DialogueStarted { window: 7 };
+        hooks.observe(&event);
+        hooks.observe(&event);
+        assert_eq!(running.load(Ordering::Acquire), 1);
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while running.load(Ordering::Acquire) > 0 && Instant::now() < deadline {
+            thread::sleep(POLL_INTERVAL);
+        }
+        assert_eq!(running.load(Ordering::Acquire), 0);
+        assert!(hooks.hooks[0].acquire().is_some());
+    }
+}
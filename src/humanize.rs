@@ -0,0 +1,222 @@
+use std::time::Duration;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::Deserialize;
+
+use crate::automation::Coordinate;
+
+/// 模拟人工操作的配置，对应配置文件中的 `[humanize]`
+///
+/// 所有范围均为 `[最小值, 最大值]`，单位为毫秒
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct HumanizeConfig {
+    pub enabled: bool,
+    /// 随机数种子，设置后每次运行的随机序列相同
+    pub seed: Option<u64>,
+    /// 按下鼠标的时长
+    pub press_ms: (u64, u64),
+    /// 点击前等待的时间
+    pub delay_ms: (u64, u64),
+    /// 点击位置偏离选项中心的最大距离，占选项框一半宽高的比例
+    pub offset: f64,
+    /// 沿缓动曲线移动鼠标，而不是直接跳到选项上
+    pub move_path: bool,
+    /// 移动鼠标的总时长
+    pub move_ms: (u64, u64),
+    /// 移动路径上的点数
+    pub move_steps: u32,
+}
+
+impl Default for HumanizeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            seed: None,
+            press_ms: (60, 120),
+            delay_ms: (0, 150),
+            offset: 0.6,
+            move_path: false,
+            move_ms: (120, 300),
+            move_steps: 20,
+        }
+    }
+}
+
+/// 未开启时按下鼠标的时长，经测试，正常的间隔大概在 75~100ms 左右
+const DEFAULT_PRESS: Duration = Duration::from_millis(50);
+
+/// 为点击生成随机的时长、位置和鼠标路径，未开启时与直接点击相同
+pub struct Humanizer {
+    config: HumanizeConfig,
+    rng: StdRng,
+}
+
+impl Humanizer {
+    pub fn new(config: HumanizeConfig) -> Self {
+        let rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        Self { config, rng }
+    }
+
+    pub fn config(&self) -> &HumanizeConfig {
+        &self.config
+    }
+
+    /// 按下鼠标的时长
+    pub fn press_duration(&mut self) -> Duration {
+        if !self.config.enabled {
+            return DEFAULT_PRESS;
+        }
+        Duration::from_millis(self.sample(self.config.press_ms))
+    }
+
+    /// 点击前等待的时间
+    pub fn click_delay(&mut self) -> Duration {
+        if !self.config.enabled {
+            return Duration::ZERO;
+        }
+        Duration::from_millis(self.sample(self.config.delay_ms))
+    }
+
+    /// 在选项框内选择点击的位置，越靠近中心的概率越大
+    pub fn click_point(&mut self, coordinate: Coordinate) -> (u32, u32) {
+        let ((left, top), (right, bottom)) = coordinate;
+        let center = ((left + right) / 2, (top + bottom) / 2);
+        if !self.config.enabled {
+            return center;
+        }
+
+        let offset = self.config.offset.clamp(0.0, 1.0);
+        let mut jitter = |low: u32, high: u32, center: u32| {
+            let half = (high - low) as f64 / 2.0 * offset;
+            // 两个均匀分布的平均值为三角分布，集中在中心附近
+            let ratio = (self.rng.gen_range(-1.0..=1.0) + self.rng.gen_range(-1.0..=1.0)) / 2.0;
+            (center as f64 + ratio * half)
+                .round()
+                .clamp(low as f64, high as f64) as u32
+        };
+        (jitter(left, right, center.0), jitter(top, bottom, center.1))
+    }
+
+    /// 从 `from` 移动到 `to` 经过的点和每一步之间的间隔，不包含起点，最后一个点为 `to`
+    ///
+    /// 未开启或不沿路径移动时只有终点
+    pub fn mouse_path(&mut self, from: (u32, u32), to: (u32, u32)) -> Vec<((u32, u32), Duration)> {
+        if !self.config.enabled || !self.config.move_path || self.config.move_steps < 2 {
+            return vec![(to, Duration::ZERO)];
+        }
+
+        let steps = self.config.move_steps;
+        let interval = Duration::from_millis(self.sample(self.config.move_ms)) / steps;
+        let (x0, y0) = (from.0 as f64, from.1 as f64);
+        let (x1, y1) = (to.0 as f64, to.1 as f64);
+
+        // 二次贝塞尔曲线，控制点在连线的垂直方向上随机偏移，使路径略微弯曲
+        let bend = self.rng.gen_range(-0.2..=0.2);
+        let control = (
+            (x0 + x1) / 2.0 - (y1 - y0) * bend,
+            (y0 + y1) / 2.0 + (x1 - x0) * bend,
+        );
+
+        (1..=steps)
+            .map(|step| {
+                let t = ease_in_out(step as f64 / steps as f64);
+                let point = if step == steps {
+                    to
+                } else {
+                    let x = (1.0 - t).powi(2) * x0 + 2.0 * (1.0 - t) * t * control.0 + t * t * x1;
+                    let y = (1.0 - t).powi(2) * y0 + 2.0 * (1.0 - t) * t * control.1 + t * t * y1;
+                    (x.round().max(0.0) as u32, y.round().max(0.0) as u32)
+                };
+                (point, interval)
+            })
+            .collect()
+    }
+
+    fn sample(&mut self, (low, high): (u64, u64)) -> u64 {
+        if high <= low {
+            low
+        } else {
+            self.rng.gen_range(low..=high)
+        }
+    }
+}
+
+/// 三次缓动，开始和结束时较慢
+fn ease_in_out(t: f64) -> f64 {
+    if t < 0.5 {
+        4.0 * t * t * t
+    } else {
+        1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOX: Coordinate = ((100, 200), (300, 260));
+
+    fn config(seed: u64) -> HumanizeConfig {
+        HumanizeConfig {
+            enabled: true,
+            seed: Some(seed),
+            move_path: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_same_sequence() {
+        let mut a = Humanizer::new(config(42));
+        let mut b = Humanizer::new(config(42));
+        for _ in 0..10 {
+            assert_eq!(a.click_point(BOX), b.click_point(BOX));
+            assert_eq!(a.press_duration(), b.press_duration());
+            assert_eq!(a.click_delay(), b.click_delay());
+            assert_eq!(
+                a.mouse_path((0, 0), (500, 400)),
+                b.mouse_path((0, 0), (500, 400))
+            );
+        }
+    }
+
+    #[test]
+    fn click_point_stays_inside_box() {
+        let mut humanizer = Humanizer::new(HumanizeConfig {
+            offset: 1.0,
+            ..config(7)
+        });
+        let ((left, top), (right, bottom)) = BOX;
+        for _ in 0..1000 {
+            let (x, y) = humanizer.click_point(BOX);
+            assert!((left..=right).contains(&x), "x = {}", x);
+            assert!((top..=bottom).contains(&y), "y = {}", y);
+        }
+    }
+
+    #[test]
+    fn mouse_path_ends_at_target() {
+        let mut humanizer = Humanizer::new(config(3));
+        for to in [(500, 400), (0, 0), (1919, 1079)] {
+            let path = humanizer.mouse_path((960, 540), to);
+            assert_eq!(path.len(), 20);
+            assert_eq!(path.last().map(|(point, _)| *point), Some(to));
+        }
+    }
+
+    #[test]
+    fn disabled_clicks_center_directly() {
+        let mut humanizer = Humanizer::new(HumanizeConfig::default());
+        assert_eq!(humanizer.click_point(BOX), (200, 230));
+        assert_eq!(humanizer.press_duration(), DEFAULT_PRESS);
+        assert_eq!(humanizer.click_delay(), Duration::ZERO);
+        assert_eq!(
+            humanizer.mouse_path((0, 0), (500, 400)),
+            vec![((500, 400), Duration::ZERO)]
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use std::{
+    collections::HashMap,
+    env, fmt,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        OnceLock,
+    },
+};
+
+use serde::Deserialize;
+use windows::Win32::Globalization::GetUserDefaultLocaleName;
+
+/// 界面语言
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub enum Lang {
+    #[default]
+    #[serde(rename = "zh-CN", alias = "zh")]
+    ZhCn,
+    #[serde(rename = "en-US", alias = "en")]
+    EnUs,
+}
+
+impl Lang {
+    const ALL: [Lang; 2] = [Lang::ZhCn, Lang::EnUs];
+
+    /// 根据语言标签（例如 `zh-CN`、`en_US.UTF-8`）选择语言，只看语言部分
+    pub fn from_tag(tag: &str) -> Option<Lang> {
+        let language = tag
+            .split(['-', '_', '.'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match language.as_str() {
+            "zh" => Some(Lang::ZhCn),
+            "en" => Some(Lang::EnUs),
+            _ => None,
+        }
+    }
+
+    /// 系统的语言，依次读取 `LC_ALL`、`LANG` 环境变量和 Windows 的用户区域设置
+    pub fn system() -> Option<Lang> {
+        ["LC_ALL", "LANG"]
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .find_map(|tag| Lang::from_tag(&tag))
+            .or_else(|| Lang::from_tag(&user_locale()?))
+    }
+
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Lang::ZhCn => "zh-CN",
+            Lang::EnUs => "en-US",
+        }
+    }
+
+    fn source(&self) -> &'static str {
+        match self {
+            Lang::ZhCn => include_str!("../assets/i18n/zh-CN.toml"),
+            Lang::EnUs => include_str!("../assets/i18n/en-US.toml"),
+        }
+    }
+}
+
+impl FromStr for Lang {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Lang::from_tag(s).ok_or_else(|| format!("unsupported language: {}", s))
+    }
+}
+
+impl fmt::Display for Lang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.tag())
+    }
+}
+
+fn user_locale() -> Option<String> {
+    // LOCALE_NAME_MAX_LENGTH
+    let mut buffer = [0u16; 85];
+    let len = unsafe { GetUserDefaultLocaleName(&mut buffer) };
+    // 返回的长度包含结尾的 0，失败时为 0
+    (len > 1).then(|| String::from_utf16_lossy(&buffer[..len as usize - 1]))
+}
+
+static LANG: AtomicU8 = AtomicU8::new(0);
+
+/// 当前的界面语言
+pub fn lang() -> Lang {
+    Lang::ALL[LANG.load(Ordering::Relaxed) as usize]
+}
+
+pub fn set_lang(lang: Lang) {
+    LANG.store(lang as u8, Ordering::Relaxed);
+}
+
+/// 依次使用 `preferred`、系统语言和简体中文
+pub fn init(preferred: Option<Lang>) {
+    set_lang(preferred.or_else(Lang::system).unwrap_or_default());
+}
+
+type Catalog = HashMap<String, &'static str>;
+
+fn catalog(lang: Lang) -> &'static Catalog {
+    static CATALOGS: [OnceLock<Catalog>; 2] = [OnceLock::new(), OnceLock::new()];
+    CATALOGS[lang as usize].get_or_init(|| {
+        let mut catalog = Catalog::new();
+        match toml::from_str::<toml::Table>(lang.source()) {
+            Ok(table) => flatten(&mut catalog, "", table),
+            Err(e) => log::error!("{} 的翻译文件有误：{}", lang, e),
+        }
+        catalog
+    })
+}
+
+/// 把嵌套的表展开为 `section.key` 形式的键
+fn flatten(catalog: &mut Catalog, prefix: &str, table: toml::Table) {
+    for (key, value) in table {
+        let key = format!("{}{}", prefix, key);
+        match value {
+            toml::Value::String(text) => {
+                // 翻译只加载一次，泄漏的内存在整个运行期间都会使用
+                catalog.insert(key, Box::leak(text.into_boxed_str()));
+            }
+            toml::Value::Table(table) => flatten(catalog, &format!("{}.", key), table),
+            _ => {}
+        }
+    }
+}
+
+/// 当前语言中 `key` 对应的文本，缺少时使用简体中文，都没有时返回 `key` 本身
+pub fn text(key: &str) -> &str {
+    catalog(lang())
+        .get(key)
+        .or_else(|| catalog(Lang::ZhCn).get(key))
+        .copied()
+        .unwrap_or(key)
+}
+
+/// 把 `key` 对应文本中的 `{name}` 替换为参数
+pub fn format(key: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+    let mut output = text(key).to_string();
+    for (name, value) in args {
+        output = output.replace(&format!("{{{}}}", name), &value.to_string());
+    }
+    output
+}
+
+/// 翻译文本：`tr!("error.io", message = e)`
+#[macro_export]
+macro_rules! tr {
+    ($key:expr) => {
+        $crate::i18n::text($key).to_string()
+    };
+    ($key:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::format(
+            $key,
+            &[$((stringify!($name), &$value as &dyn ::std::fmt::Display)),+],
+        )
+    };
+}
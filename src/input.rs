@@ -1,41 +1,89 @@
 use std::{
-    sync::{Mutex, OnceLock},
+    sync::{Mutex, PoisonError},
     thread,
     time::Duration,
 };
 
-use enigo::{Button, Direction, Enigo, Mouse, Settings};
+use enigo::{Button, Direction, Enigo, Key, Keyboard, Mouse, Settings};
 
-use crate::error::SrPlotResult;
+use crate::{
+    error::{SrPlotError, SrPlotResult},
+    tr,
+};
+
+/// 第一次使用时才创建，创建失败时下一次使用再重试
+static ENIGO_INS: Mutex<Option<Enigo>> = Mutex::new(None);
 
-static ENIGO_INS: OnceLock<Mutex<Enigo>> = OnceLock::new();
+fn with_enigo<T>(f: impl FnOnce(&mut Enigo) -> SrPlotResult<T>) -> SrPlotResult<T> {
+    // 其他线程在使用 Enigo 时 panic 不影响 Enigo 本身的状态
+    let mut slot = ENIGO_INS.lock().unwrap_or_else(PoisonError::into_inner);
+    let enigo = match slot.take() {
+        Some(enigo) => enigo,
+        None => Enigo::new(&Settings::default())
+            .map_err(|e| SrPlotError::InputUnavailable(e.to_string()))?,
+    };
+    let enigo = slot.insert(enigo);
+    f(enigo)
+}
 
-fn get_enigo<'a>() -> std::sync::MutexGuard<'a, Enigo> {
-    let enigo_locker =
-        ENIGO_INS.get_or_init(|| Mutex::new(Enigo::new(&Settings::default()).unwrap()));
-    enigo_locker.lock().unwrap()
+/// 鼠标输入的后端，默认为 [`EnigoInput`]
+pub trait InputBackend: Send + Sync {
+    /// 在当前位置点击鼠标左键
+    fn click(&self) -> SrPlotResult<()>;
+    /// 在当前位置按下鼠标左键，`hold` 后松开
+    fn click_for(&self, hold: Duration) -> SrPlotResult<()> {
+        let _ = hold;
+        self.click()
+    }
+    /// 松开鼠标左键
+    fn release(&self) -> SrPlotResult<()>;
+    /// 鼠标在屏幕上的位置
+    fn position(&self) -> (u32, u32);
+    /// 把鼠标移动到屏幕上的 `(x, y)`
+    fn move_mouse(&self, x: u32, y: u32) -> SrPlotResult<()>;
+    /// 按下并松开键盘上的 `key`
+    fn press_key(&self, key: char) -> SrPlotResult<()> {
+        let _ = key;
+        Err(SrPlotError::User(tr!("error.keyboard_unsupported")))
+    }
+    /// 是否可以向不在前台的窗口发送输入，为 `true` 时会同时处理所有找到的窗口
+    fn supports_background(&self) -> bool {
+        false
+    }
 }
 
-pub struct Input;
+/// 通过 enigo 模拟系统鼠标输入
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EnigoInput;
 
-impl Input {
-    pub fn click() -> SrPlotResult<()> {
-        let mut enigo = get_enigo();
-        enigo.button(Button::Left, Direction::Press)?;
+impl InputBackend for EnigoInput {
+    fn click(&self) -> SrPlotResult<()> {
         // 经测试，正常的间隔大概在 75~100ms 左右
-        thread::sleep(Duration::from_millis(50));
-        enigo.button(Button::Left, Direction::Release)?;
-        Ok(())
+        self.click_for(Duration::from_millis(50))
+    }
+
+    fn click_for(&self, hold: Duration) -> SrPlotResult<()> {
+        with_enigo(|enigo| {
+            enigo.button(Button::Left, Direction::Press)?;
+            thread::sleep(hold);
+            enigo.button(Button::Left, Direction::Release)?;
+            Ok(())
+        })
+    }
+
+    fn release(&self) -> SrPlotResult<()> {
+        with_enigo(|enigo| Ok(enigo.button(Button::Left, Direction::Release)?))
+    }
+
+    fn position(&self) -> (u32, u32) {
+        with_enigo(|enigo| Ok(enigo.location()?)).map_or((0, 0), |loc| (loc.0 as u32, loc.1 as u32))
     }
 
-    pub fn position() -> (u32, u32) {
-        get_enigo()
-            .location()
-            .map_or((0, 0), |loc| (loc.0 as u32, loc.1 as u32))
+    fn move_mouse(&self, x: u32, y: u32) -> SrPlotResult<()> {
+        with_enigo(|enigo| Ok(enigo.move_mouse(x as i32, y as i32, enigo::Coordinate::Abs)?))
     }
 
-    pub fn move_mouse(x: u32, y: u32) -> SrPlotResult<()> {
-        get_enigo().move_mouse(x as i32, y as i32, enigo::Coordinate::Abs)?;
-        Ok(())
+    fn press_key(&self, key: char) -> SrPlotResult<()> {
+        with_enigo(|enigo| Ok(enigo.key(Key::Unicode(key), Direction::Click)?))
     }
 }
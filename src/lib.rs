@@ -1,7 +1,27 @@
+pub mod analyze;
 mod automation;
-mod error;
-mod input;
+mod builder;
+pub mod cancel;
+pub mod config;
+pub mod console;
+pub mod error;
+pub mod event;
+pub mod governor;
+pub mod hook;
+pub mod humanize;
+pub mod i18n;
+pub mod input;
+pub mod logging;
+pub mod metrics;
 pub mod plot;
-mod screenshot;
+pub mod recorder;
+pub mod screenshot;
+pub mod stats;
+mod target;
 mod utils;
+pub mod webhook;
 mod xcap;
+
+pub use automation::{Candidate, Coordinate, MouseOutsidePolicy, Region, ScaleRange};
+pub use builder::{load_image, PlotBuilder};
+pub use utils::{TitlePattern, WindowMatcher, KNOWN_TITLES};
@@ -0,0 +1,495 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock, PoisonError},
+};
+
+use log::{
+    kv::{self, Key, Value, VisitSource},
+    LevelFilter, Log, Metadata, Record,
+};
+use regex::Regex;
+use serde::Deserialize;
+use simple_logger::SimpleLogger;
+use time::{
+    format_description::well_known::Rfc3339, macros::format_description, Date, OffsetDateTime,
+};
+
+use crate::{
+    error::{SrPlotError, SrPlotResult},
+    tr,
+};
+
+/// 日志文件的配置，对应配置文件中的 `[log]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    /// 日志文件的路径，不设置则只输出到控制台
+    pub file: Option<PathBuf>,
+    /// 写入文件的格式
+    pub format: LogFormat,
+    /// 写入文件的最低级别
+    pub level: LevelFilter,
+    /// 文件超过该大小（MB）时轮换，为 0 时不限制
+    pub max_size_mb: u64,
+    /// 每天轮换一次
+    pub daily: bool,
+    /// 保留的旧日志文件数量
+    pub keep: usize,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            file: None,
+            format: LogFormat::Text,
+            level: LevelFilter::Info,
+            max_size_mb: 10,
+            daily: true,
+            keep: 7,
+        }
+    }
+}
+
+/// 日志文件的格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// 与控制台相同的文本，附带 `key=value` 形式的字段
+    #[default]
+    Text,
+    /// 每行一个 JSON 对象，字段与消息、级别等并列
+    Json,
+}
+
+/// 同时输出到控制台和日志文件
+struct Logger {
+    console: SimpleLogger,
+    file: Option<Mutex<LogFile>>,
+    file_level: LevelFilter,
+    format: LogFormat,
+}
+
+/// 初始化全局的日志，`console` 负责控制台输出，`config` 中设置了文件时同时写入文件
+pub fn init(console: SimpleLogger, config: &LogConfig) -> SrPlotResult<()> {
+    let file = match &config.file {
+        Some(path) => Some(Mutex::new(LogFile::open(path.clone(), config)?)),
+        None => None,
+    };
+    let file_level = if file.is_some() {
+        config.level
+    } else {
+        LevelFilter::Off
+    };
+    log::set_max_level(console.max_level().max(file_level));
+    let logger = Logger {
+        console,
+        file,
+        file_level,
+        format: config.format,
+    };
+    // SimpleLogger 自己初始化时会开启 Windows 控制台的颜色支持
+    simple_logger::set_up_windows_color_terminal();
+    log::set_boxed_logger(Box::new(logger)).map_err(|e| SrPlotError::User(e.to_string()))
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.console.enabled(metadata) || metadata.level() <= self.file_level
+    }
+
+    fn log(&self, record: &Record) {
+        self.console.log(record);
+
+        let Some(file) = &self.file else {
+            return;
+        };
+        if record.level() > self.file_level {
+            return;
+        }
+        let now = OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc());
+        let line = match self.format {
+            LogFormat::Text => format_text(record, now),
+            LogFormat::Json => format_json(record, now),
+        };
+        let mut file = file.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Err(e) = file.write_line(&line, now.date()) {
+            eprintln!("{}", tr!("logging.write_failed", message = e));
+        }
+    }
+
+    fn flush(&self) {
+        self.console.flush();
+        if let Some(file) = &self.file {
+            let _ = file
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .file
+                .flush();
+        }
+    }
+}
+
+/// 去掉 `colored` 加上的 ANSI 颜色代码
+pub fn strip_ansi(text: &str) -> std::borrow::Cow<'_, str> {
+    static ANSI: OnceLock<Regex> = OnceLock::new();
+    ANSI.get_or_init(|| Regex::new("\x1b\\[[0-9;]*m").expect("ANSI 正则表达式有误"))
+        .replace_all(text, "")
+}
+
+fn format_text(record: &Record, now: OffsetDateTime) -> String {
+    let time = now
+        .format(format_description!(
+            "[year]-[month]-[day] [hour]:[minute]:[second].[subsecond digits:3]"
+        ))
+        .unwrap_or_default();
+    let mut line = format!(
+        "{} {:<5} [{}] {}",
+        time,
+        record.level(),
+        record.target(),
+        strip_ansi(&record.args().to_string())
+    );
+    let mut fields = TextFields(&mut line);
+    let _ = record.key_values().visit(&mut fields);
+    line
+}
+
+fn format_json(record: &Record, now: OffsetDateTime) -> String {
+    let mut object = serde_json::Map::new();
+    object.insert(
+        "time".into(),
+        now.format(&Rfc3339).unwrap_or_default().into(),
+    );
+    object.insert("level".into(), record.level().as_str().into());
+    object.insert("target".into(), record.target().into());
+    object.insert(
+        "message".into(),
+        strip_ansi(&record.args().to_string()).into_owned().into(),
+    );
+    let mut fields = JsonFields(&mut object);
+    let _ = record.key_values().visit(&mut fields);
+    serde_json::Value::Object(object).to_string()
+}
+
+struct TextFields<'a>(&'a mut String);
+
+impl<'kvs> VisitSource<'kvs> for TextFields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        self.0
+            .push_str(&format!(" {}={}", key, strip_ansi(&value.to_string())));
+        Ok(())
+    }
+}
+
+struct JsonFields<'a>(&'a mut serde_json::Map<String, serde_json::Value>);
+
+impl<'kvs> VisitSource<'kvs> for JsonFields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        // 保留数字和布尔值的类型，其他值转换为字符串
+        let value = if let Some(value) = value.to_bool() {
+            value.into()
+        } else if let Some(value) = value.to_u64() {
+            value.into()
+        } else if let Some(value) = value.to_i64() {
+            value.into()
+        } else if let Some(value) = value.to_f64() {
+            serde_json::Number::from_f64(value).map_or(serde_json::Value::Null, Into::into)
+        } else {
+            strip_ansi(&value.to_string()).into_owned().into()
+        };
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+/// 按大小和日期轮换的日志文件
+///
+/// 轮换时把当前文件重命名为 `名称.时间.扩展名`，只保留最新的 `keep` 个旧文件
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    date: Date,
+    max_size: u64,
+    daily: bool,
+    keep: usize,
+}
+
+impl LogFile {
+    fn open(path: PathBuf, config: &LogConfig) -> io::Result<Self> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let metadata = file.metadata()?;
+        // 已有的文件按最后修改的日期计算，使跨天重新启动时也会轮换
+        let date = metadata
+            .modified()
+            .ok()
+            .map(|modified| {
+                let modified = OffsetDateTime::from(modified);
+                match time::UtcOffset::current_local_offset() {
+                    Ok(offset) => modified.to_offset(offset),
+                    Err(_) => modified,
+                }
+                .date()
+            })
+            .unwrap_or_else(|| OffsetDateTime::now_utc().date());
+        Ok(Self {
+            path,
+            file,
+            size: metadata.len(),
+            date,
+            max_size: config.max_size_mb * 1024 * 1024,
+            daily: config.daily,
+            keep: config.keep,
+        })
+    }
+
+    fn write_line(&mut self, line: &str, today: Date) -> io::Result<()> {
+        let len = line.len() as u64 + 1;
+        if should_rotate(
+            self.size,
+            len,
+            self.max_size,
+            self.daily.then_some((self.date, today)),
+        ) {
+            self.rotate()?;
+            self.date = today;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.size += len;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let now = OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc());
+        let time = now
+            .format(format_description!(
+                "[year][month][day]-[hour][minute][second]"
+            ))
+            .unwrap_or_default();
+        let (stem, extension) = split_name(&self.path);
+
+        // 同一秒内多次轮换时加上序号
+        let mut archive = self
+            .path
+            .with_file_name(format!("{}.{}{}", stem, time, extension));
+        let mut index = 1;
+        while archive.exists() {
+            archive = self
+                .path
+                .with_file_name(format!("{}.{}-{}{}", stem, time, index, extension));
+            index += 1;
+        }
+        fs::rename(&self.path, &archive)?;
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        self.remove_old(&stem, &extension)
+    }
+
+    /// 删除超出保留数量的旧文件，文件名中的时间越早越先删除
+    fn remove_old(&self, stem: &str, extension: &str) -> io::Result<()> {
+        let dir = match self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            Some(dir) => dir.to_path_buf(),
+            None => PathBuf::from("."),
+        };
+        let prefix = format!("{}.", stem);
+        let mut archives: Vec<PathBuf> = fs::read_dir(&dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path != &self.path)
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with(&prefix) && name.ends_with(extension))
+            })
+            .collect();
+        // 同一秒内轮换的文件带有序号，序号越大越新
+        archives.sort_by_cached_key(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let time = name
+                .get(prefix.len()..name.len().saturating_sub(extension.len()))
+                .unwrap_or_default()
+                .to_string();
+            match time.split_at_checked(ARCHIVE_TIME_LEN) {
+                Some((time, index)) => (
+                    time.to_string(),
+                    index.trim_start_matches('-').parse::<u32>().unwrap_or(0),
+                ),
+                None => (time, 0),
+            }
+        });
+        let excess = archives.len().saturating_sub(self.keep);
+        for path in &archives[..excess] {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+/// 旧文件名中时间部分的长度，例如 `20240101-000000`
+const ARCHIVE_TIME_LEN: usize = 15;
+
+/// 写入 `len` 字节前是否需要轮换，`dates` 为按天轮换时文件的日期和今天
+fn should_rotate(size: u64, len: u64, max_size: u64, dates: Option<(Date, Date)>) -> bool {
+    // 空文件不轮换，避免单行超过上限时不断产生空文件
+    if size == 0 {
+        return false;
+    }
+    (max_size > 0 && size + len > max_size) || dates.is_some_and(|(date, today)| date != today)
+}
+
+/// 文件名拆分为主名和带点的扩展名，例如 `sr_plot.log` -> (`sr_plot`, `.log`)
+fn split_name(path: &Path) -> (String, String) {
+    let stem = path
+        .file_stem()
+        .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+    let extension = path
+        .extension()
+        .map_or_else(String::new, |ext| format!(".{}", ext.to_string_lossy()));
+    (stem, extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use colored::Colorize;
+    use log::Level;
+    use time::macros::date;
+
+    use super::*;
+
+    /// 每个测试单独使用的临时目录
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sr_plot_log_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn rotates_on_size() {
+        assert!(!should_rotate(90, 10, 100, None));
+        assert!(should_rotate(91, 10, 100, None));
+        // 不限制大小
+        assert!(!should_rotate(u64::MAX / 2, 10, 0, None));
+        // 空文件即使单行超过上限也不轮换
+        assert!(!should_rotate(0, 1000, 100, None));
+    }
+
+    #[test]
+    fn rotates_on_date_change() {
+        let today = date!(2024 - 05 - 02);
+        assert!(should_rotate(
+            10,
+            10,
+            0,
+            Some((date!(2024 - 05 - 01), today))
+        ));
+        assert!(!should_rotate(10, 10, 0, Some((today, today))));
+    }
+
+    #[test]
+    fn write_line_rotates_the_file() {
+        let dir = temp_dir("rotate");
+        let path = dir.join("sr_plot.log");
+        let mut file = LogFile::open(path.clone(), &LogConfig::default()).unwrap();
+        file.max_size = 16;
+        let today = file.date;
+        file.write_line("first line", today).unwrap();
+        file.write_line("second line", today).unwrap();
+
+        let names = names(&dir);
+        assert_eq!(names.len(), 2);
+        assert!(names[0].starts_with("sr_plot.") && names[0].ends_with(".log"));
+        assert_eq!(names[1], "sr_plot.log");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second line\n");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn retention_removes_only_the_oldest_archives() {
+        let dir = temp_dir("retention");
+        let path = dir.join("sr_plot.log");
+        let mut file = LogFile::open(path, &LogConfig::default()).unwrap();
+        file.keep = 2;
+        // 最新的两个是同一秒内轮换的文件
+        for name in [
+            "sr_plot.20240101-000000.log",
+            "sr_plot.20240102-000000.log",
+            "sr_plot.20240103-000000.log",
+            "sr_plot.20240103-000000-1.log",
+            "sr_plot.20240103-000000-2.log",
+            "other.20240101-000000.log",
+            "sr_plot.20240101-000000.txt",
+        ] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        file.remove_old("sr_plot", ".log").unwrap();
+
+        assert_eq!(
+            names(&dir),
+            [
+                "other.20240101-000000.log",
+                "sr_plot.20240101-000000.txt",
+                "sr_plot.20240103-000000-1.log",
+                "sr_plot.20240103-000000-2.log",
+                "sr_plot.log",
+            ]
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn strips_colored_escape_codes() {
+        colored::control::set_override(true);
+        let text = format!("{} {}", "错误".red().bold(), "ok".green());
+        assert!(text.contains('\x1b'));
+        assert_eq!(strip_ansi(&text), "错误 ok");
+        assert_eq!(strip_ansi("plain"), "plain");
+    }
+
+    #[test]
+    fn json_contains_fields() {
+        colored::control::set_override(true);
+        let fields = [
+            ("template", Value::from("select.png")),
+            ("score", Value::from(0.93)),
+            ("x", Value::from(960u32)),
+            ("y", Value::from(540u32)),
+            ("status", Value::from("active")),
+        ];
+        let line = format_json(
+            &Record::builder()
+                .args(format_args!("{}", "matched".red()))
+                .level(Level::Info)
+                .target("sr_plot_rs")
+                .key_values(&fields)
+                .build(),
+            OffsetDateTime::UNIX_EPOCH,
+        );
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["level"], "INFO");
+        assert_eq!(json["message"], "matched");
+        assert_eq!(json["template"], "select.png");
+        assert_eq!(json["score"], 0.93);
+        assert_eq!(json["x"], 960);
+        assert_eq!(json["y"], 540);
+        assert_eq!(json["status"], "active");
+        assert_eq!(json["time"], "1970-01-01T00:00:00Z");
+    }
+}
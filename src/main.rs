@@ -1,73 +1,171 @@
+use clap::{Parser, Subcommand};
 use simple_logger::SimpleLogger;
-use sr_plot_rs::plot::Plot;
-use std::{fmt::Write, thread};
+use sr_plot_rs::{
+    analyze,
+    config::{Config, CONFIG_PATH},
+    console,
+    i18n::{self, Lang},
+    logging::{self, LogConfig},
+    plot::Plot,
+    tr,
+};
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+    thread,
+};
 use unicode_width::UnicodeWidthStr;
 use is_elevated::is_elevated;
 
-const WELCOME: &str = r#"
-欢迎使用「崩坏：星穹铁道」自动对话程序
+/// 「崩坏：星穹铁道」自动对话程序
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// 配置文件的路径
+    #[arg(long, default_value = CONFIG_PATH)]
+    config: PathBuf,
+    /// 界面语言（zh-CN 或 en-US），默认使用配置文件中的 lang 或系统语言
+    #[arg(long)]
+    lang: Option<Lang>,
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
 
-请使用「管理员身份」运行此程序
-需要保持游戏窗口在前台运行
-若游戏为「窗口化」，请确保鼠标位置在游戏窗口内
-"#;
+#[derive(Subcommand)]
+enum Commands {
+    /// 离线识别截图，输出每个模板的相似度、位置和将会采取的操作
+    Analyze {
+        /// 游戏窗口客户区的截图
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// 模拟的窗口大小，例如 1280x720，默认为截图的大小
+        #[arg(long, value_parser = analyze::parse_size)]
+        size: Option<(u32, u32)>,
+        /// 以 JSON 格式输出
+        #[arg(long)]
+        json: bool,
+    },
+}
 
 fn main() {
+    let cli = Cli::parse();
+    // 配置文件有误时由 Plot 输出错误，这里使用默认值
+    let config = Config::load(&cli.config).unwrap_or_default();
+    i18n::init(cli.lang.or(config.lang));
+
+    if let Some(Commands::Analyze { files, size, json }) = cli.command {
+        std::process::exit(analyze(&cli.config, &files, size, json));
+    }
+
     if !is_elevated() {
-        println!("{}", hr("请使用「管理员身份」运行此程序\n按回车键<Enter>退出"));
+        println!("{}", hr(i18n::text("elevation")));
         let _ = std::io::stdin().read_line(&mut String::new());
         return;
     }
-    setup();
-    println!("{}", hr(WELCOME));
-    thread::spawn(|| Plot::default().run()).join().unwrap();
+    setup(&config.log);
+    println!("{}", hr(i18n::text("welcome")));
+    println!("{}", console::help());
+
+    let mut plot = Plot::default().with_config_file(cli.config);
+    let token = plot.cancellation_token();
+    if let Err(e) = ctrlc::set_handler(move || {
+        // 第二次按下 Ctrl-C 时强制退出
+        if token.is_cancelled() {
+            std::process::exit(130);
+        }
+        token.cancel();
+    }) {
+        log::error!("{}", tr!("main.ctrlc_failed", message = e));
+    }
+
+    let commands = console::spawn();
+    // 检测中的 panic 由 Plot::run 自行恢复，这里只会是处理命令或退出时的意外
+    match thread::spawn(move || plot.run(commands)).join() {
+        Ok(summary) => println!("{}", hr(&summary.to_string())),
+        Err(_) => log::error!("{}", tr!("main.thread_panicked")),
+    }
+}
+
+/// 离线识别截图，不需要管理员权限，有截图识别失败时返回 1
+fn analyze(config_path: &Path, files: &[PathBuf], size: Option<(u32, u32)>, json: bool) -> i32 {
+    let config = match Config::load(config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+    let plot = Plot::builder().config(config).build();
+
+    let mut failed = false;
+    let mut results = Vec::new();
+    for file in files {
+        let result = plot.analyze(file, size);
+        failed |= result.is_err();
+        match (result, json) {
+            (Ok(analysis), false) => println!("{}", analysis),
+            (Err(e), false) => eprintln!("{}: {}", file.display(), e),
+            (Ok(analysis), true) => {
+                results.push(serde_json::to_value(&analysis).unwrap_or_else(
+                    |e| serde_json::json!({ "file": file, "error": e.to_string() }),
+                ))
+            }
+            (Err(e), true) => {
+                results.push(serde_json::json!({ "file": file, "error": e.to_string() }))
+            }
+        }
+    }
+    if json {
+        println!("{}", serde_json::Value::Array(results));
+    }
+    i32::from(failed)
 }
 
 #[cfg(not(debug_assertions))]
-fn setup() {
-    SimpleLogger::new()
+fn setup(log_config: &LogConfig) {
+    let console = SimpleLogger::new()
         .with_level(log::LevelFilter::Info)
         .with_local_timestamps()
         .with_timestamp_format(time::macros::format_description!(
             "[year]-[month]-[day] [hour]:[minute]:[second].[subsecond digits:3]"
-        ))
-        .init()
-        .unwrap();
+        ));
+    logging::init(console, log_config)
+        .unwrap_or_else(|e| eprintln!("{}", tr!("main.log_init_failed", message = e)));
 }
 
 #[cfg(debug_assertions)]
-fn setup() {
-    SimpleLogger::new()
+fn setup(log_config: &LogConfig) {
+    let console = SimpleLogger::new()
         .with_level(log::LevelFilter::Debug)
         .with_local_timestamps()
         .with_timestamp_format(time::macros::format_description!(
             "[year]-[month]-[day] [hour]:[minute]:[second].[subsecond digits:3]"
         ))
-        .env()
-        .init()
-        .unwrap();
+        .env();
+    logging::init(console, log_config)
+        .unwrap_or_else(|e| eprintln!("{}", tr!("main.log_init_failed", message = e)));
 }
 
 fn hr(title: &str) -> String {
     let mut output = String::new();
     // 左右留下长度为 10 的空格
-    let max_length = title.split("\n").map(|line| line.width()).max().unwrap() + 22;
+    let max_length = title.split("\n").map(|line| line.width()).max().unwrap_or(0) + 22;
 
-    writeln!(&mut output, "+{}+", "-".repeat(max_length - 2)).unwrap();
+    // 写入 String 不会失败
+    let _ = writeln!(&mut output, "+{}+", "-".repeat(max_length - 2));
     for line in title.split("\n") {
         let length = line.width();
         let left_padding = (max_length - length - 2) / 2;
         let right_padding = max_length - length - left_padding - 2;
-        writeln!(
+        let _ = writeln!(
             &mut output,
             "|{}{}{}|",
             " ".repeat(left_padding),
             line,
             " ".repeat(right_padding)
-        )
-        .unwrap();
+        );
     }
-    writeln!(&mut output, "+{}+", "-".repeat(max_length - 2)).unwrap();
+    let _ = writeln!(&mut output, "+{}+", "-".repeat(max_length - 2));
 
     output
 }
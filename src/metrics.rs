@@ -0,0 +1,422 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{self, BufRead, BufReader, Write},
+    net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+    sync::{Arc, Mutex, PoisonError},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use serde::Deserialize;
+
+use crate::{cancel::CancellationToken, event::PlotEvent, plot::GameStatus, tr};
+
+/// 指标接口的配置，对应配置文件中的 `[metrics]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    pub enabled: bool,
+    /// 监听的端口，只绑定 127.0.0.1
+    pub port: u16,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 9898,
+        }
+    }
+}
+
+/// 耗时的分桶上限（秒）
+const LATENCY_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5];
+/// 相似度的分桶上限
+const SCORE_BUCKETS: &[f64] = &[0.5, 0.6, 0.7, 0.8, 0.85, 0.9, 0.95, 1.0];
+
+const STATUSES: [GameStatus; 4] = [
+    GameStatus::Uninitialized,
+    GameStatus::Active,
+    GameStatus::Inactive,
+    GameStatus::NotFound,
+];
+
+#[derive(Debug, Clone)]
+struct Histogram {
+    bounds: &'static [f64],
+    /// 每个桶（不累计）的数量，最后一个为超出所有上限的数量
+    counts: Vec<u64>,
+    sum: f64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len() + 1],
+            sum: 0.0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        let index = self
+            .bounds
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(self.bounds.len());
+        self.counts[index] += 1;
+        self.sum += value;
+    }
+
+    fn write(&self, output: &mut String, name: &str, labels: &str) {
+        let mut cumulative = 0;
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            cumulative += count;
+            let _ = writeln!(
+                output,
+                "{}_bucket{{{}le=\"{}\"}} {}",
+                name,
+                with_comma(labels),
+                bound,
+                cumulative
+            );
+        }
+        cumulative += self.counts[self.bounds.len()];
+        let _ = writeln!(
+            output,
+            "{}_bucket{{{}le=\"+Inf\"}} {}",
+            name,
+            with_comma(labels),
+            cumulative
+        );
+        let braces = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{}}}", labels)
+        };
+        let _ = writeln!(output, "{}_sum{} {}", name, braces, self.sum);
+        let _ = writeln!(output, "{}_count{} {}", name, braces, cumulative);
+    }
+}
+
+fn with_comma(labels: &str) -> String {
+    if labels.is_empty() {
+        String::new()
+    } else {
+        format!("{},", labels)
+    }
+}
+
+/// 转义标签值中的 `\`、`"` 和换行
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[derive(Debug, Clone)]
+struct Registry {
+    ticks: Histogram,
+    screenshots: Histogram,
+    matching: Histogram,
+    scores: BTreeMap<String, Histogram>,
+    dialogues: u64,
+    clicks: u64,
+    choices: BTreeMap<usize, u64>,
+    errors: BTreeMap<&'static str, u64>,
+    status: GameStatus,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self {
+            ticks: Histogram::new(LATENCY_BUCKETS),
+            screenshots: Histogram::new(LATENCY_BUCKETS),
+            matching: Histogram::new(LATENCY_BUCKETS),
+            scores: BTreeMap::new(),
+            dialogues: 0,
+            clicks: 0,
+            choices: BTreeMap::new(),
+            errors: BTreeMap::new(),
+            status: GameStatus::Uninitialized,
+        }
+    }
+}
+
+/// 根据 [`PlotEvent`] 汇总的运行指标，可以输出为 Prometheus 的文本格式
+#[derive(Default)]
+pub struct Metrics {
+    registry: Mutex<Registry>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录一个事件，通常通过 [`EventBus::on_event`](crate::event::EventBus::on_event) 调用
+    pub fn observe(&self, event: &PlotEvent) {
+        let mut registry = self.lock();
+        match event {
+            PlotEvent::TickCompleted { elapsed } => registry.ticks.observe(elapsed.as_secs_f64()),
+            PlotEvent::FrameCaptured { elapsed, .. } => {
+                registry.screenshots.observe(elapsed.as_secs_f64())
+            }
+            PlotEvent::TemplateMatched {
+                template,
+                score,
+                elapsed,
+                ..
+            } => {
+                registry.matching.observe(elapsed.as_secs_f64());
+                // 截图为纯色时相似度为 NaN 或无穷大
+                if score.is_finite() {
+                    registry
+                        .scores
+                        .entry(template.clone())
+                        .or_insert_with(|| Histogram::new(SCORE_BUCKETS))
+                        .observe(*score);
+                }
+            }
+            PlotEvent::DialogueStarted { .. } => registry.dialogues += 1,
+            // 与运行统计一致，按键选择选项也算作一次点击
+            PlotEvent::ClickSent { .. } | PlotEvent::KeyPressed { .. } => registry.clicks += 1,
+            PlotEvent::ChoiceSelected { index, .. } => {
+                *registry.choices.entry(*index).or_default() += 1
+            }
+            PlotEvent::Error { kind, .. } => *registry.errors.entry(kind).or_default() += 1,
+            PlotEvent::StatusChanged {
+                window: None, to, ..
+            } => registry.status = *to,
+            _ => {}
+        }
+    }
+
+    /// 以 Prometheus 的文本格式输出所有指标
+    pub fn render(&self) -> String {
+        let registry = self.lock().clone();
+        let mut output = String::new();
+
+        let mut histogram = |name: &str, help: &str, histogram: &Histogram| {
+            let _ = writeln!(output, "# HELP {} {}", name, help);
+            let _ = writeln!(output, "# TYPE {} histogram", name);
+            histogram.write(&mut output, name, "");
+        };
+        histogram(
+            "sr_plot_tick_duration_seconds",
+            "Duration of one detection pass over all windows",
+            &registry.ticks,
+        );
+        histogram(
+            "sr_plot_screenshot_duration_seconds",
+            "Duration of taking and converting a screenshot",
+            &registry.screenshots,
+        );
+        histogram(
+            "sr_plot_match_duration_seconds",
+            "Duration of matching a template",
+            &registry.matching,
+        );
+
+        let name = "sr_plot_match_score";
+        let _ = writeln!(output, "# HELP {} Best match score per template", name);
+        let _ = writeln!(output, "# TYPE {} histogram", name);
+        for (template, histogram) in &registry.scores {
+            histogram.write(
+                &mut output,
+                name,
+                &format!("template=\"{}\"", escape(template)),
+            );
+        }
+
+        let mut counter = |name: &str, help: &str, value: u64| {
+            let _ = writeln!(output, "# HELP {} {}", name, help);
+            let _ = writeln!(output, "# TYPE {} counter", name);
+            let _ = writeln!(output, "{} {}", name, value);
+        };
+        counter(
+            "sr_plot_dialogues_total",
+            "Dialogues entered",
+            registry.dialogues,
+        );
+        counter(
+            "sr_plot_clicks_total",
+            "Mouse clicks and key presses sent",
+            registry.clicks,
+        );
+
+        let name = "sr_plot_choices_total";
+        let _ = writeln!(output, "# HELP {} Options selected by index", name);
+        let _ = writeln!(output, "# TYPE {} counter", name);
+        for (index, count) in &registry.choices {
+            let _ = writeln!(output, "{}{{index=\"{}\"}} {}", name, index, count);
+        }
+
+        let name = "sr_plot_errors_total";
+        let _ = writeln!(output, "# HELP {} Errors by kind", name);
+        let _ = writeln!(output, "# TYPE {} counter", name);
+        for (kind, count) in &registry.errors {
+            let _ = writeln!(output, "{}{{kind=\"{}\"}} {}", name, kind, count);
+        }
+
+        let name = "sr_plot_game_status";
+        let _ = writeln!(
+            output,
+            "# HELP {} Current game status, 1 for the current one",
+            name
+        );
+        let _ = writeln!(output, "# TYPE {} gauge", name);
+        for status in STATUSES {
+            let _ = writeln!(
+                output,
+                "{}{{status=\"{}\"}} {}",
+                name,
+                status.key(),
+                u8::from(status == registry.status)
+            );
+        }
+
+        output
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Registry> {
+        self.registry.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// 在 `127.0.0.1:port` 上提供 `GET /metrics`，`cancel` 取消后停止监听
+pub fn serve(
+    metrics: Arc<Metrics>,
+    port: u16,
+    cancel: CancellationToken,
+) -> io::Result<(SocketAddr, JoinHandle<()>)> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    let addr = listener.local_addr()?;
+    // 非阻塞地等待连接，以便定期检查是否已经取消
+    listener.set_nonblocking(true)?;
+    let handle = thread::spawn(move || {
+        while !cancel.is_cancelled() {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(e) = respond(stream, &metrics) {
+                        log::debug!("{}", tr!("metrics.respond_failed", message = e));
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    cancel.sleep(Duration::from_millis(100));
+                }
+                Err(e) => {
+                    log::warn!("{}", tr!("metrics.accept_failed", message = e));
+                    cancel.sleep(Duration::from_millis(100));
+                }
+            }
+        }
+    });
+    Ok((addr, handle))
+}
+
+fn respond(stream: TcpStream, metrics: &Metrics) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+    stream.set_write_timeout(Some(Duration::from_secs(2)))?;
+
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // 读完请求头，避免客户端在发送完之前收到响应后连接被重置
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && header.trim_end() != "" {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (status, content_type, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => (
+            "200 OK",
+            "text/plain; version=0.0.4; charset=utf-8",
+            metrics.render(),
+        ),
+        (Some("GET"), Some(_)) => ("404 Not Found", "text/plain", "not found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "method not allowed\n".to_string(),
+        ),
+    };
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    /// 发送一个请求，返回完整的响应
+    fn request(addr: SocketAddr, request_line: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        write!(stream, "{}\r\nHost: localhost\r\n\r\n", request_line).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn serves_metrics_over_http() {
+        let metrics = Arc::new(Metrics::new());
+        metrics.observe(&PlotEvent::ClickSent {
+            window: 1,
+            x: 10,
+            y: 20,
+        });
+        metrics.observe(&PlotEvent::KeyPressed {
+            window: 1,
+            key: 'f',
+        });
+        metrics.observe(&PlotEvent::ChoiceSelected {
+            window: 1,
+            index: 2,
+        });
+        metrics.observe(&PlotEvent::StatusChanged {
+            window: None,
+            from: GameStatus::Uninitialized,
+            to: GameStatus::Active,
+        });
+
+        let cancel = CancellationToken::new();
+        let (addr, handle) = serve(metrics, 0, cancel.clone()).unwrap();
+        assert!(addr.ip().is_loopback());
+        assert_ne!(addr.port(), 0);
+
+        let response = request(addr, "GET /metrics HTTP/1.1");
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK"));
+        assert!(head.contains("Content-Type: text/plain; version=0.0.4"));
+        assert!(body.contains("# TYPE sr_plot_tick_duration_seconds histogram"));
+        assert!(body.contains("sr_plot_clicks_total 2\n"));
+        assert!(body.contains("sr_plot_choices_total{index=\"2\"} 1\n"));
+        assert!(body.contains("sr_plot_game_status{status=\"active\"} 1\n"));
+
+        let response = request(addr, "GET /other HTTP/1.1");
+        assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+        let response = request(addr, "POST /metrics HTTP/1.1");
+        assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed"));
+
+        cancel.cancel();
+        handle.join().unwrap();
+    }
+}
@@ -1,127 +1,508 @@
 use std::{
-    thread,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{mpsc::Receiver, Arc},
     time::{Duration, Instant},
 };
 
-use crate::{automation::Automation, error::SrPlotResult, utils::get_window, xcap::Window};
+use crate::{
+    analyze::{self, Analysis},
+    automation::Automation,
+    builder::PlotBuilder,
+    cancel::CancellationToken,
+    config::Config,
+    console::Command,
+    error::{Recovery, SrPlotError, SrPlotResult},
+    event::{EventBus, PlotEvent},
+    hook::Hooks,
+    i18n,
+    input::InputBackend,
+    metrics::{self, Metrics},
+    screenshot::FrameSource,
+    stats::{Stats, StatsReport, WindowReport},
+    target::Target,
+    tr,
+    utils::panic_message,
+    webhook::Webhooks,
+};
 
 use colored::Colorize;
 
-pub type ImageFile = (&'static str, Vec<u8>);
+/// 模板图片的名称和编码后的数据
+pub type ImageFile = (String, Vec<u8>);
+/// 截图区域，分别为 x、y、宽、高占窗口大小的比例
 pub type CropRatio = (f32, f32, f32, f32);
 
-const START_IMAGE_CROP: CropRatio = (122.0 / 1920.0, 31.0 / 1080.0, 98.0 / 1920.0, 58.0 / 1080.0);
-const SELECT_IMAGE_CROP: CropRatio = (
+pub const START_IMAGE_CROP: CropRatio =
+    (122.0 / 1920.0, 31.0 / 1080.0, 98.0 / 1920.0, 58.0 / 1080.0);
+pub const SELECT_IMAGE_CROP: CropRatio = (
     1290.0 / 1920.0,
     442.0 / 1080.0,
     74.0 / 1920.0,
     400.0 / 1080.0,
 );
 
+/// 检测时发生 panic 后，等待多久再重新开始
+const PANIC_BACKOFF: Duration = Duration::from_secs(1);
+/// 退出时最多等待多久让 webhook 发送完
+const WEBHOOK_SHUTDOWN_WAIT: Duration = Duration::from_secs(10);
+
 pub struct Plot {
     select_img: ImageFile,
-    game_title_name: String,
     start_img: Vec<ImageFile>,
+    frame_source: Arc<dyn FrameSource>,
+    input: Arc<dyn InputBackend>,
     game_status: GameStatus,
-    auto: Automation,
+    targets: Vec<Target>,
+    /// 检测次数和所有窗口的整体状态，以及已经关闭的窗口的统计，现有窗口的统计在 `targets` 中
+    stats: Stats,
+    config: Config,
+    config_path: Option<PathBuf>,
+    paused: bool,
+    /// 本次运行中检测时发生 panic 的次数
+    panics: u32,
+    cancel: CancellationToken,
+    events: EventBus,
+    /// 第一次 `run` 时根据配置启动指标接口、webhook 和钩子，之后的 `run` 沿用，避免重复注册
+    services_started: bool,
+    webhooks: Option<Arc<Webhooks>>,
 }
 
 impl Plot {
+    /// 只按窗口标题查找游戏窗口，不检查所属的进程
     pub fn new(game_title_name: String, select_img: ImageFile, start_img: Vec<ImageFile>) -> Self {
+        Self::builder()
+            .window_title(game_title_name)
+            .window_process(None)
+            .select_image(select_img)
+            .start_images(start_img)
+            .build()
+    }
+
+    /// 使用默认的窗口标题、模板图片和配置创建 [`PlotBuilder`]
+    pub fn builder() -> PlotBuilder {
+        PlotBuilder::default()
+    }
+
+    pub(crate) fn from_builder(builder: PlotBuilder) -> Self {
+        let PlotBuilder {
+            select_img,
+            start_img,
+            config,
+            frame_source,
+            input,
+        } = builder;
+
         Self {
-            auto: Automation::new(&game_title_name),
             select_img,
-            game_title_name,
             start_img,
+            frame_source,
+            input,
             game_status: GameStatus::Uninitialized,
+            targets: Vec::new(),
+            stats: Stats::default(),
+            config,
+            config_path: None,
+            paused: false,
+            panics: 0,
+            cancel: CancellationToken::new(),
+            events: EventBus::new(),
+            services_started: false,
+            webhooks: None,
         }
     }
 
-    pub fn run(&mut self) {
-        loop {
-            if let Err(e) = self.check_game_status() {
-                log::error!("{}", format!("{}", e).red().bold());
-            }
-            thread::sleep(Duration::from_millis(500));
+    /// 订阅运行过程中的事件，见 [`PlotEvent`]
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// 从配置文件读取配置，`reload` 命令会重新读取该文件
+    pub fn with_config_file(mut self, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        match Config::load(&path) {
+            Ok(config) => self.apply_config(config),
+            Err(e) => log::error!("{}", format!("{}", e).red().bold()),
+        }
+        self.config_path = Some(path);
+        self
+    }
+
+    fn apply_config(&mut self, mut config: Config) {
+        // 自定义的匹配函数无法写在配置文件中，重新加载时保留
+        if config.window.custom.is_none() {
+            config.window.custom = self.config.window.custom.take();
+        }
+        for target in &mut self.targets {
+            target.auto.set_dry_run(config.dry_run);
+            target
+                .auto
+                .recorder_mut()
+                .set_config(config.recorder.clone());
+            target.auto.set_humanize(config.humanize.clone());
+            target.auto.set_restore_cursor(config.restore_cursor);
+            target.auto.set_governor(config.governor.clone());
+            target
+                .auto
+                .set_mouse_outside(config.mouse_outside, config.safe_point);
         }
+        self.config = config;
     }
 
-    fn check_game_status(&mut self) -> SrPlotResult<()> {
-        match get_window(&self.game_title_name) {
-            Some(window) if window.is_active() => {
-                self.game_status.set(GameStatus::Active);
-                self.autoplot(&window)?;
+    /// 使用当前的模板和配置离线识别一张截图，`window_size` 为模拟的窗口大小，默认为截图的大小
+    pub fn analyze(
+        &self,
+        path: impl AsRef<Path>,
+        window_size: Option<(u32, u32)>,
+    ) -> SrPlotResult<Analysis> {
+        analyze::analyze(
+            path.as_ref(),
+            window_size,
+            &self.config,
+            &self.select_img,
+            &self.start_img,
+        )
+    }
+
+    /// 用于从其他线程（例如 Ctrl-C 处理函数）停止 `run`
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    /// 运行自动对话，每次检测之间处理收到的命令，被取消后释放输入并返回本次运行的统计
+    pub fn run(&mut self, commands: Receiver<Command>) -> StatsReport {
+        self.start_services();
+        while !self.cancel.is_cancelled() {
+            for command in commands.try_iter() {
+                self.handle_command(command);
+            }
+            if self.cancel.is_cancelled() {
+                break;
             }
-            None => {
-                self.game_status.set(GameStatus::NotFound);
+            if !self.paused {
+                let timer = Instant::now();
+                self.stats.record_tick();
+                self.supervise();
+                self.events.emit(PlotEvent::TickCompleted {
+                    elapsed: timer.elapsed(),
+                });
             }
-            _ => self.game_status.set(GameStatus::Inactive),
+            self.cancel
+                .sleep(Duration::from_millis(self.config.interval));
         }
 
-        Ok(())
+        log::info!("{}", tr!("plot.exiting").yellow().bold());
+        if let Err(e) = self.input.release() {
+            log::error!("{}", format!("{}", e).red().bold());
+        }
+
+        let report = self.stats();
+        if let Some(dir) = &self.config.stats_dir {
+            match report.save(dir) {
+                Ok(path) => log::info!("{}", tr!("plot.stats_saved", path = path.display())),
+                Err(e) => log::error!(
+                    "{}",
+                    tr!("plot.stats_save_failed", message = e).red().bold()
+                ),
+            }
+        }
+
+        self.events.emit(PlotEvent::Stopped);
+        if let Some(webhooks) = &self.webhooks {
+            webhooks.flush(WEBHOOK_SHUTDOWN_WAIT);
+        }
+        report
     }
 
-    fn autoplot(&mut self, window: &Window) -> SrPlotResult<()> {
-        let time = Instant::now();
+    /// 启动配置中的指标接口、webhook 和钩子并订阅事件，只在第一次调用时生效
+    fn start_services(&mut self) {
+        if self.services_started {
+            return;
+        }
+        self.services_started = true;
+        if self.config.metrics.enabled {
+            self.start_metrics();
+        }
+        self.webhooks = Webhooks::start(self.config.webhooks.clone()).map(Arc::new);
+        if let Some(webhooks) = &self.webhooks {
+            let webhooks = webhooks.clone();
+            self.events.on_event(move |event| webhooks.observe(event));
+        }
+        if let Some(hooks) = Hooks::new(self.config.hooks.clone()) {
+            self.events.on_event(move |event| hooks.observe(event));
+        }
+    }
 
-        self.auto.take_screenshot(START_IMAGE_CROP.into())?;
+    /// 启动指标接口，`run` 被取消后停止
+    fn start_metrics(&self) {
+        let metrics = Arc::new(Metrics::new());
+        match metrics::serve(
+            metrics.clone(),
+            self.config.metrics.port,
+            self.cancel.clone(),
+        ) {
+            Ok((addr, _)) => {
+                self.events.on_event(move |event| metrics.observe(event));
+                log::info!("{}", tr!("plot.metrics_listening", addr = addr));
+            }
+            Err(e) => log::error!("{}", tr!("plot.metrics_failed", message = e).red().bold()),
+        }
+    }
 
-        // 缩放大小，匹配窗口分辨率
-        let scale_factor = window.width() as f64 / 1920.0;
-        let scale_range = if scale_factor < 1.0 {
-            Some((
-                ((scale_factor - 0.05) * 10.0).round() / 10.0,
-                ((scale_factor + 0.05) * 10.0).round() / 10.0,
-            ))
-        } else {
-            None
+    /// 检测一次游戏状态，发生意外的 panic 时记录下来并释放输入，下一次检测照常进行
+    fn supervise(&mut self) {
+        let payload = match panic::catch_unwind(AssertUnwindSafe(|| self.check_game_status())) {
+            Ok(()) => return,
+            Err(payload) => payload,
         };
 
-        for img in &self.start_img {
-            if self.auto.find_element(img, 0.9, scale_range)?.is_some() {
-                self.auto.take_screenshot(SELECT_IMAGE_CROP.into())?;
-                match self.auto.find_element(
-                    &self.select_img,
-                    0.88, // 遇到过 0.89 匹配不上，所以降低 threshold
-                    scale_range,
-                )? {
-                    // 有选项就点击选项
-                    Some(coordinate) => self.auto.click_with_coordinate(coordinate)?,
-                    // 没选项就随便点
-                    None => self.auto.click()?,
+        self.panics += 1;
+        let e = SrPlotError::Panic(panic_message(payload.as_ref()));
+        log::error!(
+            "{}",
+            tr!("plot.panic_restart", message = e, count = self.panics)
+                .red()
+                .bold()
+        );
+        self.stats.record_error(&e);
+        self.events.emit(PlotEvent::Error {
+            window: None,
+            kind: e.kind(),
+            message: e.to_string(),
+            logged: true,
+        });
+        // panic 时可能正按着鼠标
+        if let Err(e) = self.input.release() {
+            log::error!("{}", format!("{}", e).red().bold());
+        }
+        self.cancel.sleep(PANIC_BACKOFF);
+    }
+
+    /// 当前的运行统计，同时处理多个窗口时附带每个仍然存在的窗口的统计
+    pub fn stats(&self) -> StatsReport {
+        let mut total = self.stats.clone();
+        for target in &self.targets {
+            total.merge(target.auto.stats());
+        }
+        let mut report = total.report();
+        if self.targets.len() > 1 {
+            report.windows = self
+                .targets
+                .iter()
+                .map(|target| WindowReport {
+                    id: target.id(),
+                    title: target.window.title.clone(),
+                    stats: target.auto.stats().report(),
+                })
+                .collect();
+        }
+        report
+    }
+
+    fn handle_command(&mut self, command: Command) {
+        match command {
+            Command::Pause => {
+                self.paused = true;
+                log::info!("{}", tr!("plot.paused").yellow().bold());
+            }
+            Command::Resume => {
+                self.paused = false;
+                log::info!("{}", tr!("plot.resumed").green().bold());
+            }
+            Command::Status => {
+                log::info!(
+                    "{}",
+                    tr!(
+                        "plot.status",
+                        window = self.config.window,
+                        status = self.game_status.name(),
+                        paused = tr!(if self.paused { "plot.yes" } else { "plot.no" }),
+                        dry_run = tr!(if self.config.dry_run {
+                            "plot.on"
+                        } else {
+                            "plot.off"
+                        }),
+                    )
+                );
+                for target in &self.targets {
+                    log::info!(
+                        "  {}",
+                        tr!(
+                            "plot.status_window",
+                            title = target.window.title,
+                            id = target.id(),
+                            status = target.status.name()
+                        )
+                    );
                 }
-                break;
             }
+            Command::Stats => log::info!("{}\n{}", tr!("plot.stats"), self.stats()),
+            Command::DryRun(dry_run) => {
+                self.config.dry_run = dry_run;
+                for target in &mut self.targets {
+                    target.auto.set_dry_run(dry_run);
+                }
+                log::info!(
+                    "{}",
+                    tr!(if dry_run {
+                        "plot.dry_run_on"
+                    } else {
+                        "plot.dry_run_off"
+                    })
+                );
+            }
+            Command::Dump => {
+                if self.config.recorder.frames == 0 {
+                    log::warn!("{}", tr!("plot.recorder_disabled").yellow().bold());
+                } else {
+                    let dumped = self
+                        .targets
+                        .iter()
+                        .filter(|target| target.dump_recorder("manual"))
+                        .count();
+                    if dumped == 0 {
+                        log::info!("{}", tr!("plot.nothing_recorded"));
+                    }
+                }
+            }
+            Command::Reload => {
+                if let Some(path) = self.config_path.clone() {
+                    match Config::load(path) {
+                        Ok(config) => self.apply_config(config),
+                        Err(e) => log::error!("{}", format!("{}", e).red().bold()),
+                    }
+                }
+                for target in &mut self.targets {
+                    target.auto.clear_cache();
+                }
+                log::info!("{}", tr!("plot.reloaded").green().bold());
+            }
+            Command::Quit => {
+                log::info!("{}", tr!("plot.quit").yellow().bold());
+                self.cancel.cancel();
+            }
+        }
+    }
+
+    fn check_game_status(&mut self) {
+        let mut windows = self.frame_source.find_windows(&self.config.window);
+        if !self.config.multi_window {
+            windows.truncate(1);
+        }
+        // 输入后端支持后台输入时，未激活的窗口也照常处理
+        let background = self.config.multi_window && self.input.supports_background();
+        let show_window = windows.len() > 1;
+
+        // 窗口关闭后（例如重启游戏，新窗口的句柄不同）合并统计并丢弃，避免内存不断增长
+        let stats = &mut self.stats;
+        self.targets.retain_mut(|target| {
+            if windows.iter().any(|window| window.id == target.id()) {
+                return true;
+            }
+            target.set_status(GameStatus::NotFound);
+            target.set_in_dialogue(false);
+            target.clear_error();
+            stats.merge(target.auto.stats());
+            false
+        });
+
+        let mut fatal = false;
+        for window in windows {
+            let index = match self.targets.iter().position(|t| t.id() == window.id) {
+                Some(index) => index,
+                None => {
+                    let mut auto = Automation::new(
+                        window.id,
+                        self.frame_source.clone(),
+                        self.input.clone(),
+                        self.events.clone(),
+                        self.cancel.clone(),
+                    );
+                    auto.set_dry_run(self.config.dry_run);
+                    auto.recorder_mut().set_config(self.config.recorder.clone());
+                    auto.set_humanize(self.config.humanize.clone());
+                    auto.set_restore_cursor(self.config.restore_cursor);
+                    auto.set_governor(self.config.governor.clone());
+                    auto.set_mouse_outside(self.config.mouse_outside, self.config.safe_point);
+                    self.targets
+                        .push(Target::new(window.clone(), auto, self.events.clone()));
+                    self.targets.len() - 1
+                }
+            };
+            let target = &mut self.targets[index];
+            target.window = window;
+
+            if target.window.is_active || background {
+                target.set_status(if target.window.is_active {
+                    GameStatus::Active
+                } else {
+                    GameStatus::Inactive
+                });
+                if target.is_backing_off() {
+                    continue;
+                }
+                match target.autoplot(&self.config, &self.select_img, &self.start_img) {
+                    Ok(()) => target.clear_error(),
+                    Err(e) => {
+                        target.report_error(&e, show_window);
+                        match e.recovery() {
+                            Recovery::Retry => {}
+                            Recovery::Backoff(duration) => target.back_off(duration),
+                            Recovery::Stop => fatal = true,
+                        }
+                    }
+                }
+            } else {
+                target.set_status(GameStatus::Inactive);
+                target.set_in_dialogue(false);
+            }
+        }
+
+        if fatal {
+            log::error!("{}", tr!("plot.fatal").red().bold());
+            self.cancel.cancel();
         }
-        log::debug!("执行完毕！总耗时：{}ms", time.elapsed().as_millis());
-        Ok(())
+
+        let status = if self
+            .targets
+            .iter()
+            .any(|target| target.status == GameStatus::Active)
+        {
+            GameStatus::Active
+        } else if self
+            .targets
+            .iter()
+            .any(|target| target.status == GameStatus::Inactive)
+        {
+            GameStatus::Inactive
+        } else {
+            GameStatus::NotFound
+        };
+        self.set_status(status);
+    }
+
+    fn set_status(&mut self, status: GameStatus) {
+        let previous = self.game_status;
+        if self.game_status.set(status) {
+            self.events.emit(PlotEvent::StatusChanged {
+                window: None,
+                from: previous,
+                to: status,
+            });
+        }
+        self.stats.enter_status(status.key());
     }
 }
 
 impl Default for Plot {
     fn default() -> Self {
-        let image_files = vec![
-            ("start.png", include_bytes!("../assets/start.png").to_vec()),
-            (
-                "start_ps5.png",
-                include_bytes!("../assets/start_ps5.png").to_vec(),
-            ),
-            (
-                "start_xbox.png",
-                include_bytes!("../assets/start_xbox.png").to_vec(),
-            ),
-        ];
-
-        let select_image = (
-            "select.png",
-            include_bytes!("../assets/select.png").to_vec(),
-        );
-        Plot::new("崩坏：星穹铁道".to_string(), select_image, image_files)
+        Self::builder().build()
     }
 }
 
+/// 游戏窗口的状态
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
-enum GameStatus {
+pub enum GameStatus {
     Uninitialized,
     Active,
     Inactive,
@@ -129,23 +510,49 @@ enum GameStatus {
 }
 
 impl GameStatus {
-    fn set(&mut self, status: GameStatus) {
+    /// 状态改变时返回 `true`
+    fn set(&mut self, status: GameStatus) -> bool {
         if *self != status {
             *self = status;
             self.log();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// 用于统计的状态名称
+    pub(crate) fn key(&self) -> &'static str {
+        match self {
+            GameStatus::Uninitialized => "uninitialized",
+            GameStatus::Active => "active",
+            GameStatus::Inactive => "inactive",
+            GameStatus::NotFound => "not_found",
         }
     }
 
+    pub(crate) fn name(&self) -> &'static str {
+        i18n::text(match self {
+            GameStatus::Uninitialized => "status.uninitialized",
+            GameStatus::Active => "status.active",
+            GameStatus::Inactive => "status.inactive",
+            GameStatus::NotFound => "status.not_found",
+        })
+    }
+
     fn log(&self) {
+        let message = tr!(&format!("status_log.{}", self.key()));
         match self {
-            GameStatus::Active => log::info!("{}", "游戏窗口已激活！正在执行中……".green().bold()),
+            GameStatus::Active => log::info!(status = self.key(); "{}", message.green().bold()),
             GameStatus::Inactive => {
-                log::warn!("{}", "检测到游戏窗口未激活".blue().bold())
+                log::warn!(status = self.key(); "{}", message.blue().bold())
             }
             GameStatus::NotFound => {
-                log::warn!("{}", "未检测到游戏窗口，等待游戏启动……".cyan().bold())
+                log::warn!(status = self.key(); "{}", message.cyan().bold())
+            }
+            GameStatus::Uninitialized => {
+                log::error!(status = self.key(); "{}", message.red().bold())
             }
-            GameStatus::Uninitialized => log::error!("{}", "未知错误".red().bold()),
         }
     }
 }
@@ -0,0 +1,196 @@
+use std::{
+    collections::VecDeque,
+    fs,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use time::{macros::format_description, OffsetDateTime};
+
+use crate::{
+    automation::{Coordinate, Region},
+    error::SrPlotResult,
+    event::WindowId,
+};
+
+/// 飞行记录仪的配置，对应配置文件中的 `[recorder]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RecorderConfig {
+    /// 在内存中保留最近的多少张截图，为 0 时不记录
+    pub frames: usize,
+    /// 导出的目录，每次导出会在其中新建一个带时间的文件夹
+    pub dir: PathBuf,
+    /// 发生错误时自动导出
+    pub on_error: bool,
+    /// 每次点击选项后自动导出
+    pub on_choice: bool,
+}
+
+impl Default for RecorderConfig {
+    fn default() -> Self {
+        Self {
+            frames: 0,
+            dir: PathBuf::from("recordings"),
+            on_error: true,
+            on_choice: false,
+        }
+    }
+}
+
+/// 一次模板匹配的结果
+#[derive(Debug, Clone, Serialize)]
+pub struct Detection {
+    pub template: String,
+    pub score: f64,
+    /// 匹配成功时目标在屏幕上的位置
+    pub coordinate: Option<Coordinate>,
+}
+
+/// 根据截图采取的操作
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum Action {
+    /// 在鼠标当前位置点击
+    Click { x: u32, y: u32 },
+    /// 点击第 `index` 个选项
+    Choice { index: usize, x: u32, y: u32 },
+    /// dry-run 模式下跳过了点击
+    Skipped,
+}
+
+struct Frame {
+    unix_ms: u128,
+    image: RgbaImage,
+    region: Region,
+    detections: Vec<Detection>,
+    action: Option<Action>,
+}
+
+#[derive(Serialize)]
+struct FrameMeta<'a> {
+    file: String,
+    unix_ms: u128,
+    /// 截图在屏幕上的区域：x、y、宽、高
+    region: (u32, u32, u32, u32),
+    detections: &'a [Detection],
+    action: Option<Action>,
+}
+
+#[derive(Serialize)]
+struct DumpMeta<'a> {
+    reason: &'a str,
+    window: WindowId,
+    frames: Vec<FrameMeta<'a>>,
+}
+
+/// 在内存中保留最近的截图、识别结果和操作，用于事后复现问题
+pub struct FlightRecorder {
+    config: RecorderConfig,
+    window: WindowId,
+    frames: VecDeque<Frame>,
+}
+
+impl FlightRecorder {
+    pub fn new(window: WindowId, config: RecorderConfig) -> Self {
+        Self {
+            config,
+            window,
+            frames: VecDeque::new(),
+        }
+    }
+
+    pub fn config(&self) -> &RecorderConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: RecorderConfig) {
+        self.config = config;
+        while self.frames.len() > self.config.frames {
+            self.frames.pop_front();
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.frames > 0
+    }
+
+    /// 记录一张新的截图，之后的识别结果和操作都属于这张截图
+    pub(crate) fn push(&mut self, image: &RgbaImage, region: Region) {
+        if !self.is_enabled() {
+            return;
+        }
+        if self.frames.len() >= self.config.frames {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(Frame {
+            unix_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |time| time.as_millis()),
+            image: image.clone(),
+            region,
+            detections: Vec::new(),
+            action: None,
+        });
+    }
+
+    pub(crate) fn add_detection(&mut self, detection: Detection) {
+        if let Some(frame) = self.frames.back_mut() {
+            frame.detections.push(detection);
+        }
+    }
+
+    pub(crate) fn set_action(&mut self, action: Action) {
+        if let Some(frame) = self.frames.back_mut() {
+            frame.action = Some(action);
+        }
+    }
+
+    /// 把记录的截图导出为 PNG，识别结果和操作写入 `frames.json`，没有记录时返回 `None`
+    pub fn dump(&self, reason: &str) -> SrPlotResult<Option<PathBuf>> {
+        if self.frames.is_empty() {
+            return Ok(None);
+        }
+
+        let now = OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc());
+        let time = now.format(format_description!(
+            "[year][month][day]-[hour][minute][second]-[subsecond digits:3]"
+        ))?;
+        let dir = self
+            .config
+            .dir
+            .join(format!("{}-{}-{}", time, reason, self.window));
+        fs::create_dir_all(&dir)?;
+
+        let mut frames = Vec::with_capacity(self.frames.len());
+        for (index, frame) in self.frames.iter().enumerate() {
+            let file = format!("frame-{:03}.png", index);
+            frame
+                .image
+                .save_with_format(dir.join(&file), image::ImageFormat::Png)?;
+            let Region {
+                x,
+                y,
+                width,
+                height,
+            } = frame.region;
+            frames.push(FrameMeta {
+                file,
+                unix_ms: frame.unix_ms,
+                region: (x, y, width, height),
+                detections: &frame.detections,
+                action: frame.action,
+            });
+        }
+
+        let meta = DumpMeta {
+            reason,
+            window: self.window,
+            frames,
+        };
+        fs::write(dir.join("frames.json"), serde_json::to_vec_pretty(&meta)?)?;
+        Ok(Some(dir))
+    }
+}
@@ -1,72 +1,312 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+use colored::Colorize;
 use image::{DynamicImage, RgbaImage};
+use sysinfo::System;
 
 use crate::{
     automation::Region,
     error::{SrPlotError, SrPlotResult},
     plot::CropRatio,
-    utils::get_window,
+    tr,
+    utils::{find_windows, match_window, sort_candidates, WindowCandidate, WindowMatcher},
+    xcap::{is_blank, CaptureMethod, Window, XCapError},
 };
 
-pub fn take_screenshot(
-    title: &str,
-    crop: Option<CropRatio>,
-) -> SrPlotResult<(RgbaImage, Region, f64, Region)> {
-    if let Some(window) = get_window(title) {
-        let mut screenshot = window
-            .capture_image()
-            .map_err(|e| SrPlotError::Screenshot(e.to_string()))?;
-
-        let window_region = Region::new(
-            window.x() as u32,
-            window.y() as u32,
-            window.width(),
-            window.height(),
-        );
+/// 找到的游戏窗口
+#[derive(Debug, Clone)]
+pub struct GameWindow {
+    /// 窗口的唯一标识（Windows 下为窗口句柄），由 `FrameSource` 自行解释
+    pub id: isize,
+    pub title: String,
+    /// 窗口所属进程的 id
+    pub pid: u32,
+    /// 窗口客户区在屏幕上的位置和大小
+    pub region: Region,
+    pub is_active: bool,
+}
+
+impl From<&Window> for GameWindow {
+    fn from(window: &Window) -> Self {
+        Self {
+            id: window.id(),
+            title: window.title().to_owned(),
+            pid: window.pid(),
+            region: Region::new(
+                window.x() as u32,
+                window.y() as u32,
+                window.width(),
+                window.height(),
+            ),
+            is_active: window.is_active(),
+        }
+    }
+}
+
+/// 游戏画面的来源，默认为 [`DesktopCapture`]
+pub trait FrameSource: Send + Sync {
+    /// 查找所有匹配的游戏窗口，按优先级从高到低排列
+    fn find_windows(&self, matcher: &WindowMatcher) -> Vec<GameWindow>;
+    /// 截取窗口客户区的完整画面
+    fn capture(&self, window: &GameWindow) -> SrPlotResult<RgbaImage>;
+}
+
+/// 即使缓存的窗口仍然有效，也每隔这么久重新枚举一次，以便发现新打开的窗口
+const RESCAN_INTERVAL: Duration = Duration::from_secs(30);
+/// 窗口截图连续这么多次为纯色时，才尝试截取屏幕上的窗口区域
+const BLANK_FRAMES_BEFORE_FALLBACK: u32 = 3;
 
-        // 先裁剪
-        if let Some(Region {
-            x,
-            y,
-            width,
-            height,
-        }) = crop.map(|crop| transform_crop(crop, window_region.width, window_region.height))
+/// 查找桌面上的窗口并截图
+///
+/// 找到的窗口会被缓存，之后每次只检查缓存的窗口是否仍然存在、标题是否仍然匹配、
+/// 前台是否切换到了其他匹配的窗口，都满足时不再枚举所有窗口
+#[derive(Debug, Default)]
+pub struct DesktopCapture {
+    cache: Mutex<WindowCache>,
+}
+
+#[derive(Debug, Default)]
+struct WindowCache {
+    /// 上一次找到的窗口，按优先级排列
+    candidates: Vec<WindowCandidate>,
+    /// 上一次枚举时的进程条件，改变后缓存失效
+    process: Option<String>,
+    scanned_at: Option<Instant>,
+    /// 每个窗口上一次截图使用的方式，改变时输出日志
+    methods: HashMap<isize, CaptureMethod>,
+    /// 每个窗口的窗口截图连续为纯色的次数
+    blank_frames: HashMap<isize, u32>,
+}
+
+impl WindowCache {
+    /// 更新缓存的窗口，缓存失效时返回 `false`
+    fn revalidate(&mut self, matcher: &WindowMatcher) -> bool {
+        if self.candidates.is_empty()
+            || self.process != matcher.process
+            || self
+                .scanned_at
+                .is_none_or(|time| time.elapsed() >= RESCAN_INTERVAL)
         {
-            screenshot = DynamicImage::ImageRgba8(screenshot)
-                .crop(x, y, width, height)
-                .to_rgba8();
+            return false;
+        }
+
+        for candidate in &mut self.candidates {
+            if !matches!(candidate.window.refresh(), Ok(true)) {
+                return false;
+            }
+            match matcher.title_priority(candidate.window.title()) {
+                Some(priority) => candidate.title_priority = priority,
+                None => return false,
+            }
+        }
+
+        // 前台切换到了另一个匹配的窗口，需要重新排序
+        if let Some(foreground) = Window::foreground() {
+            let id = foreground.id();
+            if !self.candidates.iter().any(|c| c.window.id() == id)
+                && match_window(matcher, &mut System::new(), foreground).is_some()
+            {
+                return false;
+            }
+        }
+
+        sort_candidates(&mut self.candidates);
+        true
+    }
+
+    fn rescan(&mut self, matcher: &WindowMatcher) {
+        let time = Instant::now();
+        let candidates = find_windows(matcher);
+        log::debug!(
+            "{}",
+            tr!("screenshot.enumerated", ms = time.elapsed().as_millis())
+        );
+
+        let ids: Vec<isize> = candidates.iter().map(|c| c.window.id()).collect();
+        let last_ids: Vec<isize> = self.candidates.iter().map(|c| c.window.id()).collect();
+        if ids != last_ids {
+            if let [first, second, ..] = candidates.as_slice() {
+                let titles: Vec<String> = candidates
+                    .iter()
+                    .map(|c| {
+                        tr!(
+                            "screenshot.window",
+                            title = c.window.title(),
+                            pid = c.window.pid()
+                        )
+                    })
+                    .collect();
+                log::info!(
+                    "{}",
+                    tr!(
+                        "screenshot.windows_found",
+                        count = candidates.len(),
+                        windows = titles.join(tr!("screenshot.separator").as_str()),
+                        title = first.window.title(),
+                        reason = first.reason_over(second, matcher)
+                    )
+                );
+            }
+        }
+
+        self.candidates = candidates;
+        self.process = matcher.process.clone();
+        self.scanned_at = Some(Instant::now());
+    }
+}
+
+impl DesktopCapture {
+    fn cache(&self) -> MutexGuard<'_, WindowCache> {
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// 部分全屏或 HDR 模式下窗口截图是纯黑的，此时改为截取屏幕上的窗口区域
+    ///
+    /// 剧情中的黑屏等画面本身就是纯色的，因此要连续多次为纯色才尝试，
+    /// 且屏幕上的画面也是纯色时沿用上一次的截图方式
+    fn fallback_if_blank(
+        &self,
+        target: &Window,
+        image: RgbaImage,
+        method: CaptureMethod,
+    ) -> (RgbaImage, CaptureMethod) {
+        let id = target.id();
+        let (blank_frames, last_method) = {
+            let mut cache = self.cache();
+            let count = cache.blank_frames.entry(id).or_default();
+            *count = if is_blank(&image) { *count + 1 } else { 0 };
+            (*count, cache.methods.get(&id).copied())
+        };
+        if blank_frames < BLANK_FRAMES_BEFORE_FALLBACK {
+            return (image, method);
+        }
+        match target.capture_desktop() {
+            Ok(desktop) if !is_blank(&desktop) || last_method == Some(CaptureMethod::Desktop) => {
+                (desktop, CaptureMethod::Desktop)
+            }
+            Ok(_) => (image, method),
+            Err(e) => {
+                log::debug!("{}", tr!("screenshot.desktop_failed", message = e));
+                (image, method)
+            }
+        }
+    }
+}
+
+impl FrameSource for DesktopCapture {
+    fn find_windows(&self, matcher: &WindowMatcher) -> Vec<GameWindow> {
+        let mut cache = self.cache();
+        if !cache.revalidate(matcher) {
+            cache.rescan(matcher);
         }
+        cache
+            .candidates
+            .iter()
+            .map(|candidate| GameWindow::from(&candidate.window))
+            .collect()
+    }
 
-        let mut screenshot_factor = 1.0;
-        // 分辨率过高，调整图片大小到 1080p，加速计算
-        if window_region.width > 1920 {
-            screenshot_factor = 1920.0 / window_region.width as f64;
-
-            screenshot = DynamicImage::ImageRgba8(screenshot)
-                .resize(
-                    (1920.0 * crop.map_or(1.0, |c| c.2)) as u32,
-                    (1080.0 * crop.map_or(1.0, |c| c.3)) as u32,
-                    image::imageops::FilterType::Nearest,
-                )
-                .to_rgba8();
+    fn capture(&self, window: &GameWindow) -> SrPlotResult<RgbaImage> {
+        // 优先使用本次检测中已经更新过的窗口，不在缓存中时才根据句柄重新获取
+        let cached = self
+            .cache()
+            .candidates
+            .iter()
+            .find(|c| c.window.id() == window.id)
+            .map(|c| c.window.clone());
+        let target = match cached {
+            Some(target) => target,
+            None => Window::from_id(window.id)
+                .map_err(|_| SrPlotError::WindowNotFound(window.title.clone()))?,
+        };
+        if target.is_minimized() {
+            return Err(SrPlotError::WindowMinimized(window.title.clone()));
         }
+        let (image, method) = target.capture_image().map_err(|e| match e {
+            XCapError::CaptureFailed { method, message } => SrPlotError::CaptureFailed {
+                method: method.to_string(),
+                message,
+            },
+            e => SrPlotError::CaptureFailed {
+                method: tr!("error.window_capture"),
+                message: e.to_string(),
+            },
+        })?;
+        let (image, method) = self.fallback_if_blank(&target, image, method);
 
-        Ok((
-            screenshot,
-            crop.map_or(window_region, |crop| {
-                let mut region = transform_crop(crop, window_region.width, window_region.height);
-                region.x += window_region.x;
-                region.y += window_region.y;
-                region
-            }),
-            screenshot_factor,
-            window_region,
-        ))
-    } else {
-        Err(SrPlotError::Screenshot(format!("窗口「{}」不存在", title)))
+        if self.cache().methods.insert(window.id, method) != Some(method) {
+            if method == CaptureMethod::Desktop {
+                log::warn!(
+                    "{}",
+                    tr!("screenshot.desktop_fallback", title = window.title)
+                        .yellow()
+                        .bold()
+                );
+            } else {
+                log::info!(
+                    "{}",
+                    tr!("screenshot.method", title = window.title, method = method)
+                );
+            }
+        }
+        Ok(image)
     }
 }
 
-pub fn transform_crop(crop: CropRatio, w: u32, h: u32) -> Region {
+pub(crate) fn take_screenshot(
+    source: &dyn FrameSource,
+    window: &GameWindow,
+    crop: Option<CropRatio>,
+) -> SrPlotResult<(RgbaImage, Region, f64, Region)> {
+    let mut screenshot = source.capture(window)?;
+
+    let window_region = window.region;
+
+    // 先裁剪
+    if let Some(Region {
+        x,
+        y,
+        width,
+        height,
+    }) = crop.map(|crop| transform_crop(crop, window_region.width, window_region.height))
+    {
+        screenshot = DynamicImage::ImageRgba8(screenshot)
+            .crop(x, y, width, height)
+            .to_rgba8();
+    }
+
+    let mut screenshot_factor = 1.0;
+    // 分辨率过高，调整图片大小到 1080p，加速计算
+    if window_region.width > 1920 {
+        screenshot_factor = 1920.0 / window_region.width as f64;
+
+        screenshot = DynamicImage::ImageRgba8(screenshot)
+            .resize(
+                (1920.0 * crop.map_or(1.0, |c| c.2)) as u32,
+                (1080.0 * crop.map_or(1.0, |c| c.3)) as u32,
+                image::imageops::FilterType::Nearest,
+            )
+            .to_rgba8();
+    }
+
+    Ok((
+        screenshot,
+        crop.map_or(window_region, |crop| {
+            let mut region = transform_crop(crop, window_region.width, window_region.height);
+            region.x += window_region.x;
+            region.y += window_region.y;
+            region
+        }),
+        screenshot_factor,
+        window_region,
+    ))
+}
+
+pub(crate) fn transform_crop(crop: CropRatio, w: u32, h: u32) -> Region {
     Region::new(
         (crop.0 * w as f32) as u32,
         (crop.1 * h as f32) as u32,
@@ -0,0 +1,309 @@
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use time::{macros::format_description, OffsetDateTime};
+
+use crate::{
+    error::{SrPlotError, SrPlotResult},
+    tr,
+};
+
+/// 每种耗时最多保留的样本数，超出后覆盖最旧的样本
+const MAX_SAMPLES: usize = 100_000;
+
+/// 一次运行中的统计数据
+#[derive(Debug, Clone)]
+pub struct Stats {
+    started_at: Instant,
+    ticks: u64,
+    dialogues: u64,
+    clicks: u64,
+    choices: BTreeMap<usize, u64>,
+    screenshot: Timing,
+    matching: Timing,
+    errors: BTreeMap<&'static str, u64>,
+    status_time: BTreeMap<&'static str, Duration>,
+    current_status: Option<(&'static str, Instant)>,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self {
+            started_at: Instant::now(),
+            ticks: 0,
+            dialogues: 0,
+            clicks: 0,
+            choices: BTreeMap::new(),
+            screenshot: Timing::default(),
+            matching: Timing::default(),
+            errors: BTreeMap::new(),
+            status_time: BTreeMap::new(),
+            current_status: None,
+        }
+    }
+}
+
+impl Stats {
+    pub fn record_tick(&mut self) {
+        self.ticks += 1;
+    }
+
+    pub fn record_dialogue(&mut self) {
+        self.dialogues += 1;
+    }
+
+    pub fn record_click(&mut self) {
+        self.clicks += 1;
+    }
+
+    /// 记录点击了第 `index` 个选项（从上往下，从 0 开始）
+    pub fn record_choice(&mut self, index: usize) {
+        *self.choices.entry(index).or_default() += 1;
+    }
+
+    pub fn record_screenshot(&mut self, elapsed: Duration) {
+        self.screenshot.record(elapsed);
+    }
+
+    pub fn record_matching(&mut self, elapsed: Duration) {
+        self.matching.record(elapsed);
+    }
+
+    pub fn record_error(&mut self, error: &SrPlotError) {
+        *self.errors.entry(error.kind()).or_default() += 1;
+    }
+
+    /// 切换当前状态，累计上一个状态持续的时间
+    pub fn enter_status(&mut self, status: &'static str) {
+        if matches!(self.current_status, Some((current, _)) if current == status) {
+            return;
+        }
+        let now = Instant::now();
+        if let Some((previous, since)) = self.current_status.replace((status, now)) {
+            *self.status_time.entry(previous).or_default() += now - since;
+        }
+    }
+
+    /// 合并另一个窗口的统计，状态持续时间除外（所有窗口的整体状态由 `Plot` 单独统计）
+    pub fn merge(&mut self, other: &Stats) {
+        self.dialogues += other.dialogues;
+        self.clicks += other.clicks;
+        for (index, count) in &other.choices {
+            *self.choices.entry(*index).or_default() += count;
+        }
+        for elapsed in &other.screenshot.samples {
+            self.screenshot.record(*elapsed);
+        }
+        for elapsed in &other.matching.samples {
+            self.matching.record(*elapsed);
+        }
+        for (kind, count) in &other.errors {
+            *self.errors.entry(kind).or_default() += count;
+        }
+    }
+
+    pub fn report(&self) -> StatsReport {
+        let mut status_time = self.status_time.clone();
+        if let Some((current, since)) = self.current_status {
+            *status_time.entry(current).or_default() += since.elapsed();
+        }
+
+        StatsReport {
+            duration_secs: self.started_at.elapsed().as_secs_f64(),
+            ticks: self.ticks,
+            dialogues: self.dialogues,
+            clicks: self.clicks,
+            choices: self.choices.clone(),
+            screenshot_ms: self.screenshot.summary(),
+            matching_ms: self.matching.summary(),
+            errors: self.errors.clone(),
+            status_secs: status_time
+                .into_iter()
+                .map(|(status, time)| (status, time.as_secs_f64()))
+                .collect(),
+            windows: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Timing {
+    samples: Vec<Duration>,
+    next: usize,
+}
+
+impl Timing {
+    fn record(&mut self, elapsed: Duration) {
+        if self.samples.len() < MAX_SAMPLES {
+            self.samples.push(elapsed);
+        } else {
+            self.samples[self.next] = elapsed;
+            self.next = (self.next + 1) % MAX_SAMPLES;
+        }
+    }
+
+    fn summary(&self) -> TimingSummary {
+        let mut samples: Vec<f64> = self
+            .samples
+            .iter()
+            .map(|elapsed| elapsed.as_secs_f64() * 1000.0)
+            .collect();
+        samples.sort_by(f64::total_cmp);
+
+        let percentile = |p: f64| -> f64 {
+            if samples.is_empty() {
+                return 0.0;
+            }
+            let rank = (p / 100.0 * samples.len() as f64).ceil() as usize;
+            samples[rank.clamp(1, samples.len()) - 1]
+        };
+
+        TimingSummary {
+            count: samples.len(),
+            mean: if samples.is_empty() {
+                0.0
+            } else {
+                samples.iter().sum::<f64>() / samples.len() as f64
+            },
+            p50: percentile(50.0),
+            p90: percentile(90.0),
+            p99: percentile(99.0),
+            max: samples.last().copied().unwrap_or_default(),
+        }
+    }
+}
+
+/// 耗时统计，单位为毫秒
+#[derive(Debug, Clone, Serialize)]
+pub struct TimingSummary {
+    pub count: usize,
+    pub mean: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+/// 某一时刻的统计快照，可以序列化为 JSON
+#[derive(Debug, Clone, Serialize)]
+pub struct StatsReport {
+    pub duration_secs: f64,
+    pub ticks: u64,
+    pub dialogues: u64,
+    pub clicks: u64,
+    /// 选项序号 -> 点击次数
+    pub choices: BTreeMap<usize, u64>,
+    pub screenshot_ms: TimingSummary,
+    pub matching_ms: TimingSummary,
+    /// 错误类型 -> 次数
+    pub errors: BTreeMap<&'static str, u64>,
+    /// 游戏状态 -> 持续时间（秒）
+    pub status_secs: BTreeMap<&'static str, f64>,
+    /// 同时处理多个窗口时，每个窗口各自的统计
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub windows: Vec<WindowReport>,
+}
+
+/// 单个窗口的统计
+#[derive(Debug, Clone, Serialize)]
+pub struct WindowReport {
+    pub id: isize,
+    pub title: String,
+    #[serde(flatten)]
+    pub stats: StatsReport,
+}
+
+impl StatsReport {
+    /// 以 JSON 格式写入 `dir` 目录，文件名带有当前时间
+    pub fn save(&self, dir: impl AsRef<Path>) -> SrPlotResult<PathBuf> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+
+        let now = OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc());
+        let name = now.format(format_description!(
+            "stats-[year][month][day]-[hour][minute][second].json"
+        ))?;
+        let path = dir.join(name);
+
+        fs::write(&path, serde_json::to_vec_pretty(self)?)?;
+        Ok(path)
+    }
+}
+
+impl TimingSummary {
+    /// 按 `p50/p90/p99` 的顺序显示，单位为毫秒
+    fn percentiles(&self) -> String {
+        format!("{:.0}/{:.0}/{:.0}", self.p50, self.p90, self.p99)
+    }
+}
+
+impl fmt::Display for StatsReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.duration_secs as u64;
+        writeln!(
+            f,
+            "{}",
+            tr!(
+                "stats.duration",
+                hours = secs / 3600,
+                minutes = secs / 60 % 60,
+                seconds = secs % 60
+            )
+        )?;
+        writeln!(
+            f,
+            "{}",
+            tr!(
+                "stats.counts",
+                ticks = self.ticks,
+                dialogues = self.dialogues,
+                clicks = self.clicks,
+                choices = self.choices.values().sum::<u64>()
+            )
+        )?;
+        writeln!(
+            f,
+            "{}",
+            tr!("stats.screenshot_ms", ms = self.screenshot_ms.percentiles())
+        )?;
+        write!(
+            f,
+            "{}",
+            tr!("stats.matching_ms", ms = self.matching_ms.percentiles())
+        )?;
+        if !self.errors.is_empty() {
+            let errors: Vec<String> = self
+                .errors
+                .iter()
+                .map(|(kind, count)| format!("{} × {}", kind, count))
+                .collect();
+            write!(
+                f,
+                "\n{}",
+                tr!(
+                    "stats.errors",
+                    errors = errors.join(tr!("stats.separator").as_str())
+                )
+            )?;
+        }
+        for window in &self.windows {
+            write!(
+                f,
+                "\n{}",
+                tr!(
+                    "stats.window",
+                    title = window.title,
+                    id = window.id,
+                    dialogues = window.stats.dialogues,
+                    clicks = window.stats.clicks
+                )
+            )?;
+        }
+        Ok(())
+    }
+}
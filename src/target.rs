@@ -0,0 +1,517 @@
+use std::time::{Duration, Instant};
+
+use colored::Colorize;
+
+use crate::{
+    automation::{Automation, Candidate, Coordinate, ScaleRange},
+    config::{Config, VerifyConfig},
+    error::{SrPlotError, SrPlotResult},
+    event::{EventBus, PlotEvent},
+    plot::{GameStatus, ImageFile},
+    recorder::Action,
+    screenshot::GameWindow,
+    tr,
+};
+
+/// 一个游戏窗口，以及为它单独维护的识别状态、模板缓存和统计
+pub(crate) struct Target {
+    pub window: GameWindow,
+    pub auto: Automation,
+    pub status: GameStatus,
+    in_dialogue: bool,
+    /// 当前显示的选项，同一组选项只发布一次 `ChoiceDetected`
+    shown_choice: Option<Vec<Candidate>>,
+    last_error: Option<LastError>,
+    /// 在此之前不再处理这个窗口
+    backoff_until: Option<Instant>,
+    events: EventBus,
+}
+
+/// 同一类错误连续出现时，至少间隔这么久才再次输出
+const ERROR_REPEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+struct LastError {
+    kind: &'static str,
+    message: String,
+    logged_at: Instant,
+    /// 上次输出之后又出现的次数
+    repeats: u32,
+}
+
+impl Target {
+    pub fn new(window: GameWindow, auto: Automation, events: EventBus) -> Self {
+        Self {
+            window,
+            auto,
+            status: GameStatus::Uninitialized,
+            in_dialogue: false,
+            shown_choice: None,
+            last_error: None,
+            backoff_until: None,
+            events,
+        }
+    }
+
+    pub fn id(&self) -> isize {
+        self.window.id
+    }
+
+    pub fn set_status(&mut self, status: GameStatus) {
+        if self.status != status {
+            log::debug!(
+                window = self.window.id,
+                status = status.key();
+                "{}",
+                tr!(
+                    "target.status_changed",
+                    title = self.window.title,
+                    id = self.window.id,
+                    from = self.status.name(),
+                    to = status.name()
+                )
+            );
+            self.events.emit(PlotEvent::StatusChanged {
+                window: Some(self.window.id),
+                from: self.status,
+                to: status,
+            });
+            self.status = status;
+        }
+        self.auto.stats_mut().enter_status(status.key());
+    }
+
+    pub fn set_in_dialogue(&mut self, in_dialogue: bool) {
+        if !in_dialogue {
+            self.shown_choice = None;
+        }
+        if self.in_dialogue == in_dialogue {
+            return;
+        }
+        self.in_dialogue = in_dialogue;
+        let window = self.window.id;
+        if in_dialogue {
+            self.auto.stats_mut().record_dialogue();
+            self.events.emit(PlotEvent::DialogueStarted { window });
+        } else {
+            self.events.emit(PlotEvent::DialogueEnded { window });
+        }
+    }
+
+    /// 导出飞行记录仪中的截图，返回是否导出了
+    pub fn dump_recorder(&self, reason: &str) -> bool {
+        match self.auto.recorder().dump(reason) {
+            Ok(Some(path)) => {
+                log::info!(
+                    "{}",
+                    tr!(
+                        "target.dumped",
+                        title = self.window.title,
+                        path = path.display()
+                    )
+                );
+                true
+            }
+            Ok(None) => false,
+            Err(e) => {
+                log::error!("{}", tr!("target.dump_failed", message = e).red().bold());
+                false
+            }
+        }
+    }
+
+    /// 记录并输出这个窗口中发生的错误
+    ///
+    /// 连续重复的同一类错误每隔 [`ERROR_REPEAT_INTERVAL`] 才输出一次，期间也不会导出截图
+    ///
+    /// 按错误类型而不是错误信息判断是否重复，错误信息中可能带有鼠标位置等每次都不同的值
+    pub fn report_error(&mut self, e: &SrPlotError, show_window: bool) {
+        let message = e.to_string();
+        self.auto.stats_mut().record_error(e);
+
+        if let Some(last) = &mut self.last_error {
+            if last.kind == e.kind() && last.logged_at.elapsed() < ERROR_REPEAT_INTERVAL {
+                last.repeats += 1;
+                self.events.emit(PlotEvent::Error {
+                    window: Some(self.window.id),
+                    kind: e.kind(),
+                    message,
+                    logged: false,
+                });
+                return;
+            }
+        }
+        self.flush_repeated_error();
+
+        let prefix = if show_window {
+            format!("[{}] ", self.window.title)
+        } else {
+            String::new()
+        };
+        log::error!(kind = e.kind(), window = self.window.id; "{}", format!("{}{}", prefix, message).red().bold());
+        self.events.emit(PlotEvent::Error {
+            window: Some(self.window.id),
+            kind: e.kind(),
+            message: message.clone(),
+            logged: true,
+        });
+        self.last_error = Some(LastError {
+            kind: e.kind(),
+            message,
+            logged_at: Instant::now(),
+            repeats: 0,
+        });
+        if self.auto.recorder().config().on_error {
+            self.dump_recorder("error");
+        }
+    }
+
+    /// 暂停处理这个窗口 `duration`
+    pub fn back_off(&mut self, duration: Duration) {
+        log::debug!(
+            "{}",
+            tr!(
+                "target.back_off",
+                title = self.window.title,
+                ms = duration.as_millis()
+            )
+        );
+        self.backoff_until = Some(Instant::now() + duration);
+    }
+
+    /// 是否还在暂停处理中
+    pub fn is_backing_off(&self) -> bool {
+        self.backoff_until
+            .is_some_and(|until| Instant::now() < until)
+    }
+
+    /// 本次检测没有出错，输出被省略的重复错误的次数
+    pub fn clear_error(&mut self) {
+        self.flush_repeated_error();
+        self.last_error = None;
+    }
+
+    fn flush_repeated_error(&mut self) {
+        if let Some(last) = &self.last_error {
+            if last.repeats > 0 {
+                log::warn!(
+                    "{}",
+                    tr!(
+                        "target.repeated_error",
+                        message = last.message,
+                        count = last.repeats
+                    )
+                );
+                self.events.emit(PlotEvent::ErrorRepeated {
+                    window: self.window.id,
+                    kind: last.kind,
+                    message: last.message.clone(),
+                    count: last.repeats,
+                });
+            }
+        }
+    }
+
+    pub fn autoplot(
+        &mut self,
+        config: &Config,
+        select_img: &ImageFile,
+        start_img: &[ImageFile],
+    ) -> SrPlotResult<()> {
+        let time = Instant::now();
+        let window = self.window.clone();
+
+        self.auto
+            .take_screenshot(&window, config.start_crop.into())?;
+
+        let scale_range = scale_range(window.region.width);
+
+        let mut in_dialogue = false;
+        for img in start_img {
+            if self
+                .auto
+                .find_element(img, config.start_threshold, scale_range)?
+                .is_some()
+            {
+                in_dialogue = true;
+                self.set_in_dialogue(true);
+
+                self.auto
+                    .take_screenshot(&window, config.select_crop.into())?;
+                let candidates =
+                    self.auto
+                        .find_elements(select_img, config.select_threshold, scale_range)?;
+                match candidates
+                    .iter()
+                    .enumerate()
+                    .max_by(|a, b| a.1.score.total_cmp(&b.1.score))
+                {
+                    // 有选项就点击选项
+                    Some((best, candidate)) => {
+                        // 窗口在前台时可以按键选择默认高亮的第一个选项，不需要移动鼠标
+                        let key = config.choice_key.filter(|_| window.is_active);
+                        let index = if key.is_some() { 0 } else { best };
+                        let shown = self
+                            .shown_choice
+                            .as_deref()
+                            .is_some_and(|shown| same_candidates(shown, &candidates));
+                        if !shown {
+                            self.shown_choice = Some(candidates.clone());
+                            self.events.emit(PlotEvent::ChoiceDetected {
+                                window: window.id,
+                                candidates: candidates.clone(),
+                                chosen: index,
+                            });
+                        }
+                        if self.choose(key, candidate.coordinate)? {
+                            self.auto.stats_mut().record_choice(index);
+                            self.events.emit(PlotEvent::ChoiceSelected {
+                                window: window.id,
+                                index,
+                            });
+                            let ((left, top), (right, bottom)) = candidates[index].coordinate;
+                            self.auto.recorder_mut().set_action(Action::Choice {
+                                index,
+                                x: (left + right) / 2,
+                                y: (top + bottom) / 2,
+                            });
+                            if config.recorder.on_choice {
+                                self.dump_recorder("choice");
+                            }
+                            if config.verify.enabled {
+                                self.verify_choice(
+                                    config,
+                                    select_img,
+                                    scale_range,
+                                    &candidates,
+                                    key,
+                                    candidate.coordinate,
+                                )?;
+                            }
+                        }
+                    }
+                    // 没选项就随便点
+                    None => {
+                        self.shown_choice = None;
+                        let before = if config.verify.enabled {
+                            Some(self.auto.frame_signature()?)
+                        } else {
+                            None
+                        };
+                        if self.auto.click()? {
+                            if let Some(before) = before {
+                                self.verify_click(&config.verify, before)?;
+                            }
+                        }
+                    }
+                }
+                break;
+            }
+        }
+        self.set_in_dialogue(in_dialogue);
+        log::debug!(
+            "{}",
+            tr!("target.tick_done", ms = time.elapsed().as_millis())
+        );
+        Ok(())
+    }
+
+    /// 按键或点击选择选项，返回是否真的发送了输入
+    fn choose(&mut self, key: Option<char>, coordinate: Coordinate) -> SrPlotResult<bool> {
+        match key {
+            Some(key) => self.auto.press_key(key),
+            None => self.auto.click_with_coordinate(coordinate),
+        }
+    }
+
+    /// 点击选项后重新识别选项，选项没有消失也没有改变时重试
+    fn verify_choice(
+        &mut self,
+        config: &Config,
+        select_img: &ImageFile,
+        scale_range: Option<ScaleRange>,
+        before: &[Candidate],
+        key: Option<char>,
+        coordinate: Coordinate,
+    ) -> SrPlotResult<()> {
+        let window = self.window.clone();
+        let verify = &config.verify;
+        // 选项消失的动画结束前再次点击会跳过下一句对话，因此至少等待点击选项后的冷却时间
+        let cooldown = Duration::from_millis(config.governor.choice_cooldown_ms);
+        let mut delay = Duration::from_millis(verify.backoff_ms);
+        for attempt in 0..=verify.retries {
+            if !self.auto.sleep(delay.max(cooldown)) {
+                return Ok(());
+            }
+            self.auto
+                .take_screenshot(&window, config.select_crop.into())?;
+            let after =
+                self.auto
+                    .find_elements(select_img, config.select_threshold, scale_range)?;
+            if !same_candidates(before, &after) {
+                return Ok(());
+            }
+            if attempt == verify.retries {
+                break;
+            }
+            log::warn!("{}", tr!("target.choice_retry", attempt = attempt + 1));
+            self.retry(|target| target.choose(key, coordinate))?;
+            delay *= 2;
+        }
+        Err(SrPlotError::Unresponsive(tr!(
+            "error.choice_unchanged",
+            count = verify.retries + 1
+        )))
+    }
+
+    /// 点击对话后检查画面是否改变，没有改变时重试
+    fn verify_click(&mut self, verify: &VerifyConfig, before: u64) -> SrPlotResult<()> {
+        let mut delay = Duration::from_millis(verify.backoff_ms);
+        for attempt in 0..=verify.retries {
+            if !self.auto.sleep(delay) {
+                return Ok(());
+            }
+            if self.auto.frame_signature()? != before {
+                return Ok(());
+            }
+            if attempt == verify.retries {
+                break;
+            }
+            log::warn!("{}", tr!("target.click_retry", attempt = attempt + 1));
+            self.retry(|target| target.auto.click())?;
+            delay *= 2;
+        }
+        Err(SrPlotError::Unresponsive(tr!(
+            "error.frame_unchanged",
+            count = verify.retries + 1
+        )))
+    }
+
+    fn retry(&mut self, input: impl FnOnce(&mut Self) -> SrPlotResult<bool>) -> SrPlotResult<bool> {
+        self.auto.set_retry(true);
+        let result = input(self);
+        self.auto.set_retry(false);
+        result
+    }
+}
+
+/// 模板的缩放范围，匹配宽度为 `window_width` 的窗口分辨率
+pub(crate) fn scale_range(window_width: u32) -> Option<ScaleRange> {
+    let scale_factor = window_width as f64 / 1920.0;
+    if scale_factor < 1.0 {
+        Some((
+            ((scale_factor - 0.05) * 10.0).round() / 10.0,
+            ((scale_factor + 0.05) * 10.0).round() / 10.0,
+        ))
+    } else {
+        None
+    }
+}
+
+/// 两次识别到的选项是否相同：数量相同且位置相差不超过几个像素
+fn same_candidates(before: &[Candidate], after: &[Candidate]) -> bool {
+    const TOLERANCE: u32 = 4;
+    before.len() == after.len()
+        && before.iter().zip(after).all(|(a, b)| {
+            let ((a_left, a_top), _) = a.coordinate;
+            let ((b_left, b_top), _) = b.coordinate;
+            a_left.abs_diff(b_left) <= TOLERANCE && a_top.abs_diff(b_top) <= TOLERANCE
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use image::RgbaImage;
+
+    use super::*;
+    use crate::{
+        automation::Region, cancel::CancellationToken, input::InputBackend,
+        screenshot::FrameSource, utils::WindowMatcher,
+    };
+
+    struct NoFrame;
+
+    impl FrameSource for NoFrame {
+        fn find_windows(&self, _: &WindowMatcher) -> Vec<GameWindow> {
+            Vec::new()
+        }
+
+        fn capture(&self, _: &GameWindow) -> SrPlotResult<RgbaImage> {
+            Err(SrPlotError::NoFrameYet)
+        }
+    }
+
+    struct NoInput;
+
+    impl InputBackend for NoInput {
+        fn click(&self) -> SrPlotResult<()> {
+            Ok(())
+        }
+
+        fn release(&self) -> SrPlotResult<()> {
+            Ok(())
+        }
+
+        fn position(&self) -> (u32, u32) {
+            (0, 0)
+        }
+
+        fn move_mouse(&self, _: u32, _: u32) -> SrPlotResult<()> {
+            Ok(())
+        }
+    }
+
+    fn target(events: EventBus) -> Target {
+        let window = GameWindow {
+            id: 1,
+            title: "test".to_string(),
+            pid: 0,
+            region: Region::new(0, 0, 1920, 1080),
+            is_active: true,
+        };
+        let auto = Automation::new(
+            window.id,
+            Arc::new(NoFrame),
+            Arc::new(NoInput),
+            events.clone(),
+            CancellationToken::new(),
+        );
+        Target::new(window, auto, events)
+    }
+
+    fn mouse_outside(x: u32, y: u32) -> SrPlotError {
+        SrPlotError::MouseOutside {
+            x,
+            y,
+            window: Region::new(0, 0, 1920, 1080),
+        }
+    }
+
+    #[test]
+    fn same_kind_with_different_message_is_repeated() {
+        let events = EventBus::new();
+        let receiver = events.subscribe();
+        let mut target = target(events);
+
+        target.report_error(&mouse_outside(2000, 100), false);
+        target.report_error(&mouse_outside(2500, 300), false);
+
+        assert_eq!(target.last_error.as_ref().unwrap().repeats, 1);
+        let logged: Vec<bool> = receiver
+            .try_iter()
+            .filter_map(|event| match event {
+                PlotEvent::Error { logged, .. } => Some(logged),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(logged, [true, false]);
+
+        target.clear_error();
+        let repeated = receiver.try_iter().find_map(|event| match event {
+            PlotEvent::ErrorRepeated { kind, count, .. } => Some((kind, count)),
+            _ => None,
+        });
+        assert_eq!(repeated, Some(("mouse_outside", 1)));
+    }
+}
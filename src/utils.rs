@@ -1,9 +1,280 @@
-use crate::xcap::Window;
+use std::{any::Any, fmt, sync::Arc};
 
-pub fn get_window(title: &str) -> Option<Window> {
-    Window::all().ok().and_then(|windows| {
-        windows
-            .into_iter()
-            .find(|window| window.title().contains(title))
+use regex::Regex;
+use serde::Deserialize;
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+
+use crate::{tr, xcap::Window};
+
+/// 各语言客户端的窗口标题
+pub const KNOWN_TITLES: [&str; 5] = [
+    "崩坏：星穹铁道",
+    "崩壞：星穹鐵道",
+    "Honkai: Star Rail",
+    "崩壊：スターレイル",
+    "붕괴: 스타레일",
+];
+
+type CustomMatcher = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// 窗口标题的匹配规则
+///
+/// 配置文件中字符串表示包含匹配，`{ exact = "..." }` 表示完全相等，`{ regex = "..." }` 表示正则匹配
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "TitlePatternDef")]
+pub enum TitlePattern {
+    Contains(String),
+    Exact(String),
+    Regex(Regex),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TitlePatternDef {
+    Contains(String),
+    Exact { exact: String },
+    Regex { regex: String },
+}
+
+impl TryFrom<TitlePatternDef> for TitlePattern {
+    type Error = regex::Error;
+
+    fn try_from(value: TitlePatternDef) -> Result<Self, Self::Error> {
+        Ok(match value {
+            TitlePatternDef::Contains(title) => Self::Contains(title),
+            TitlePatternDef::Exact { exact } => Self::Exact(exact),
+            TitlePatternDef::Regex { regex } => Self::Regex(Regex::new(&regex)?),
+        })
+    }
+}
+
+impl TitlePattern {
+    pub fn matches(&self, title: &str) -> bool {
+        match self {
+            Self::Contains(pattern) => title.contains(pattern.as_str()),
+            Self::Exact(pattern) => title == pattern,
+            Self::Regex(pattern) => pattern.is_match(title),
+        }
+    }
+}
+
+impl fmt::Display for TitlePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Contains(pattern) => f.write_str(&tr!("matcher.contains", pattern = pattern)),
+            Self::Exact(pattern) => f.write_str(&tr!("matcher.exact", pattern = pattern)),
+            Self::Regex(pattern) => f.write_str(&tr!("matcher.regex", pattern = pattern)),
+        }
+    }
+}
+
+/// 判断一个窗口是否为游戏窗口，所有设置了的条件都满足才算匹配
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct WindowMatcher {
+    /// 窗口所属进程的可执行文件名（不区分大小写），不设置则不检查进程
+    pub process: Option<String>,
+    /// 窗口标题满足其中任意一条即可，排在前面的优先级更高，为空则不检查标题
+    pub titles: Vec<TitlePattern>,
+    /// 自定义的标题匹配函数，只能通过代码设置
+    #[serde(skip)]
+    pub custom: Option<CustomMatcher>,
+}
+
+impl Default for WindowMatcher {
+    fn default() -> Self {
+        Self {
+            process: Some("StarRail.exe".to_string()),
+            titles: KNOWN_TITLES
+                .iter()
+                .map(|title| TitlePattern::Contains(title.to_string()))
+                .collect(),
+            custom: None,
+        }
+    }
+}
+
+impl WindowMatcher {
+    /// 只按标题匹配
+    pub fn title(title: impl Into<String>) -> Self {
+        Self {
+            process: None,
+            titles: vec![TitlePattern::Contains(title.into())],
+            custom: None,
+        }
+    }
+
+    /// 只按进程可执行文件名匹配
+    pub fn process(process: impl Into<String>) -> Self {
+        Self {
+            process: Some(process.into()),
+            titles: Vec::new(),
+            custom: None,
+        }
+    }
+
+    pub fn with_custom(mut self, matcher: impl Fn(&str) -> bool + Send + Sync + 'static) -> Self {
+        self.custom = Some(Arc::new(matcher));
+        self
+    }
+
+    /// 窗口标题匹配时返回匹配到的规则序号，不检查标题时为 0
+    pub fn title_priority(&self, title: &str) -> Option<usize> {
+        if !self.custom.as_ref().is_none_or(|matcher| matcher(title)) {
+            return None;
+        }
+        if self.titles.is_empty() {
+            return Some(0);
+        }
+        self.titles
+            .iter()
+            .position(|pattern| pattern.matches(title))
+    }
+
+    /// 判断窗口标题是否匹配
+    pub fn matches_title(&self, title: &str) -> bool {
+        self.title_priority(title).is_some()
+    }
+
+    /// 判断进程可执行文件名是否匹配
+    pub fn matches_process(&self, process_name: Option<&str>) -> bool {
+        match (&self.process, process_name) {
+            (None, _) => true,
+            (Some(expected), Some(actual)) => expected.eq_ignore_ascii_case(actual),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+impl fmt::Debug for WindowMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WindowMatcher")
+            .field("process", &self.process)
+            .field("titles", &self.titles)
+            .field("custom", &self.custom.is_some())
+            .finish()
+    }
+}
+
+impl fmt::Display for WindowMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut conditions = Vec::new();
+        if let Some(process) = &self.process {
+            conditions.push(tr!("matcher.process", process = process));
+        }
+        if !self.titles.is_empty() {
+            let titles: Vec<String> = self.titles.iter().map(ToString::to_string).collect();
+            conditions.push(tr!(
+                "matcher.titles",
+                titles = titles.join(tr!("matcher.or").as_str())
+            ));
+        }
+        if self.custom.is_some() {
+            conditions.push(tr!("matcher.custom"));
+        }
+        if conditions.is_empty() {
+            f.write_str(&tr!("matcher.any"))
+        } else {
+            f.write_str(&conditions.join(tr!("matcher.separator").as_str()))
+        }
+    }
+}
+
+/// 查找进程的可执行文件名
+pub fn process_name(system: &mut System, pid: u32) -> Option<String> {
+    let pid = Pid::from_u32(pid);
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[pid]),
+        true,
+        ProcessRefreshKind::new(),
+    );
+    system
+        .process(pid)
+        .map(|process| process.name().to_string_lossy().into_owned())
+}
+
+/// 一个匹配的窗口以及它的优先级
+#[derive(Debug, Clone)]
+pub struct WindowCandidate {
+    pub window: Window,
+    /// 匹配到的标题规则序号，越小越优先
+    pub title_priority: usize,
+}
+
+impl WindowCandidate {
+    fn area(&self) -> u64 {
+        self.window.width() as u64 * self.window.height() as u64
+    }
+
+    /// 说明为什么选择了 `self` 而不是 `other`
+    pub fn reason_over(&self, other: &WindowCandidate, matcher: &WindowMatcher) -> String {
+        if self.window.is_active() && !other.window.is_active() {
+            tr!("matcher.reason_active")
+        } else if self.title_priority < other.title_priority {
+            match matcher.titles.get(self.title_priority) {
+                Some(pattern) => tr!("matcher.reason_title", pattern = pattern),
+                None => tr!("matcher.reason_title_any"),
+            }
+        } else if self.area() > other.area() {
+            tr!("matcher.reason_area")
+        } else {
+            tr!("matcher.reason_first")
+        }
+    }
+}
+
+/// 判断一个窗口是否匹配，匹配时返回它的优先级
+pub fn match_window(
+    matcher: &WindowMatcher,
+    system: &mut System,
+    window: Window,
+) -> Option<WindowCandidate> {
+    // 先比较标题，避免为每个窗口查询进程
+    let title_priority = matcher.title_priority(window.title())?;
+    if matcher.process.is_some()
+        && !matcher.matches_process(process_name(system, window.pid()).as_deref())
+    {
+        return None;
+    }
+    Some(WindowCandidate {
+        window,
+        title_priority,
     })
 }
+
+/// 按优先级排序：前台窗口、标题规则靠前、面积更大
+pub fn sort_candidates(candidates: &mut [WindowCandidate]) {
+    candidates.sort_by(|a, b| {
+        b.window
+            .is_active()
+            .cmp(&a.window.is_active())
+            .then(a.title_priority.cmp(&b.title_priority))
+            .then(b.area().cmp(&a.area()))
+    });
+}
+
+/// 枚举所有窗口，找出匹配的窗口并按优先级排序
+pub fn find_windows(matcher: &WindowMatcher) -> Vec<WindowCandidate> {
+    let Ok(windows) = Window::all() else {
+        return Vec::new();
+    };
+
+    let mut system = System::new();
+    let mut candidates: Vec<WindowCandidate> = windows
+        .into_iter()
+        .filter_map(|window| match_window(matcher, &mut system, window))
+        .collect();
+    sort_candidates(&mut candidates);
+    candidates
+}
+
+/// 取出 panic 时的消息，不是字符串时返回固定的说明
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        tr!("error.unknown_panic")
+    }
+}
@@ -0,0 +1,457 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, SyncSender, TrySendError},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use serde_json::Value;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{event::PlotEvent, plot::GameStatus, tr};
+
+/// 一个 webhook，对应配置文件中的 `[[webhooks]]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    /// 接收 POST 请求的地址
+    pub url: String,
+    /// 触发的事件，为空时所有事件都会发送
+    pub events: Vec<WebhookEvent>,
+    /// 请求体的模板，`{name}` 会被替换为对应的值，不设置时发送包含所有值的 JSON 对象
+    pub payload: Option<String>,
+    /// 额外的请求头
+    pub headers: BTreeMap<String, String>,
+    /// 每次请求的超时时间（毫秒）
+    pub timeout_ms: u64,
+    /// 失败后最多重试的次数
+    pub retries: u32,
+    /// 第一次重试前等待的时间（毫秒），之后每次翻倍
+    pub backoff_ms: u64,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            events: Vec::new(),
+            payload: None,
+            headers: BTreeMap::new(),
+            timeout_ms: 5000,
+            retries: 3,
+            backoff_ms: 1000,
+        }
+    }
+}
+
+/// 可以触发 webhook 的事件
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    /// 出现了选项
+    ChoiceDetected,
+    /// 对话结束
+    DialogueEnded,
+    /// 游戏窗口消失
+    WindowLost,
+    /// 程序停止运行
+    Stopped,
+    /// 同一个错误重复出现
+    RepeatedError,
+}
+
+impl WebhookEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WebhookEvent::ChoiceDetected => "choice_detected",
+            WebhookEvent::DialogueEnded => "dialogue_ended",
+            WebhookEvent::WindowLost => "window_lost",
+            WebhookEvent::Stopped => "stopped",
+            WebhookEvent::RepeatedError => "repeated_error",
+        }
+    }
+}
+
+/// 一次要发送的通知，`vars` 为可以在模板中使用的值
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub event: WebhookEvent,
+    pub vars: BTreeMap<&'static str, Value>,
+}
+
+impl Notification {
+    /// 从 [`PlotEvent`] 生成通知，不需要通知的事件返回 `None`
+    pub fn from_event(event: &PlotEvent) -> Option<Self> {
+        let (kind, mut vars) = match event {
+            PlotEvent::ChoiceDetected {
+                window,
+                candidates,
+                chosen,
+            } => (
+                WebhookEvent::ChoiceDetected,
+                BTreeMap::from([
+                    ("window", Value::from(*window)),
+                    ("count", Value::from(candidates.len())),
+                    ("chosen", Value::from(*chosen)),
+                ]),
+            ),
+            PlotEvent::DialogueEnded { window } => (
+                WebhookEvent::DialogueEnded,
+                BTreeMap::from([("window", Value::from(*window))]),
+            ),
+            PlotEvent::StatusChanged {
+                window: Some(window),
+                from,
+                to: GameStatus::NotFound,
+            } if *from != GameStatus::Uninitialized => (
+                WebhookEvent::WindowLost,
+                BTreeMap::from([("window", Value::from(*window))]),
+            ),
+            PlotEvent::Stopped => (WebhookEvent::Stopped, BTreeMap::new()),
+            PlotEvent::ErrorRepeated {
+                window,
+                kind,
+                message,
+                count,
+            } => (
+                WebhookEvent::RepeatedError,
+                BTreeMap::from([
+                    ("window", Value::from(*window)),
+                    ("kind", Value::from(*kind)),
+                    ("message", Value::from(message.as_str())),
+                    ("count", Value::from(*count)),
+                ]),
+            ),
+            _ => return None,
+        };
+
+        let text = match kind {
+            WebhookEvent::ChoiceDetected => {
+                tr!("webhook.choice_detected", count = vars["count"])
+            }
+            WebhookEvent::RepeatedError => tr!(
+                "webhook.repeated_error",
+                message = vars["message"].as_str().unwrap_or_default(),
+                count = vars["count"]
+            ),
+            _ => tr!(&format!("webhook.{}", kind.name())),
+        };
+        let now = OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc());
+        vars.insert("event", Value::from(kind.name()));
+        vars.insert("text", Value::from(text));
+        vars.insert(
+            "time",
+            Value::from(now.format(&Rfc3339).unwrap_or_default()),
+        );
+        Some(Self { event: kind, vars })
+    }
+
+    /// 生成请求体，模板中字符串的值会按 JSON 字符串转义（不含两边的引号）
+    pub fn payload(&self, template: Option<&str>) -> String {
+        let Some(template) = template else {
+            return Value::Object(
+                self.vars
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.clone()))
+                    .collect(),
+            )
+            .to_string();
+        };
+        let mut output = template.to_string();
+        for (name, value) in &self.vars {
+            let text = match value {
+                Value::String(text) => {
+                    let quoted = Value::from(text.as_str()).to_string();
+                    quoted[1..quoted.len() - 1].to_string()
+                }
+                value => value.to_string(),
+            };
+            output = output.replace(&format!("{{{}}}", name), &text);
+        }
+        output
+    }
+}
+
+/// 等待发送的通知数量上限，超出时丢弃新的通知，避免阻塞自动对话
+const QUEUE_SIZE: usize = 64;
+/// 等待队列发送完毕时检查的间隔
+const FLUSH_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// 在后台线程中发送 webhook，不会阻塞调用方，丢弃后发送线程随之退出
+pub struct Webhooks {
+    sender: SyncSender<Notification>,
+    /// 已经放入队列但还没有发送完的通知数量
+    pending: Arc<AtomicUsize>,
+}
+
+impl Webhooks {
+    /// 启动发送线程，没有配置任何 webhook 时返回 `None`
+    pub fn start(configs: Vec<WebhookConfig>) -> Option<Self> {
+        if configs.is_empty() {
+            return None;
+        }
+        let (sender, receiver) = mpsc::sync_channel::<Notification>(QUEUE_SIZE);
+        let pending = Arc::new(AtomicUsize::new(0));
+        let worker_pending = pending.clone();
+        thread::spawn(move || {
+            let tls = native_tls::TlsConnector::new()
+                .map_err(|e| log::warn!("{}", tr!("webhook.tls_failed", message = e)))
+                .ok()
+                .map(Arc::new);
+            let agents: Vec<ureq::Agent> = configs
+                .iter()
+                .map(|config| {
+                    let builder =
+                        ureq::AgentBuilder::new().timeout(Duration::from_millis(config.timeout_ms));
+                    match &tls {
+                        Some(tls) => builder.tls_connector(tls.clone()),
+                        None => builder,
+                    }
+                    .build()
+                })
+                .collect();
+            for notification in receiver {
+                for (config, agent) in configs.iter().zip(&agents) {
+                    if config.events.is_empty() || config.events.contains(&notification.event) {
+                        deliver(agent, config, &notification);
+                    }
+                }
+                worker_pending.fetch_sub(1, Ordering::SeqCst);
+            }
+        });
+        Some(Self { sender, pending })
+    }
+
+    /// 记录一个事件，需要通知时放入发送队列
+    pub fn observe(&self, event: &PlotEvent) {
+        let Some(notification) = Notification::from_event(event) else {
+            return;
+        };
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        if let Err(e) = self.sender.try_send(notification) {
+            self.pending.fetch_sub(1, Ordering::SeqCst);
+            if let TrySendError::Full(_) = e {
+                log::warn!("{}", tr!("webhook.queue_full"));
+            }
+        }
+    }
+
+    /// 等待已经排队的通知发送完毕，最多等待 `wait`，之后仍可以继续发送
+    pub fn flush(&self, wait: Duration) {
+        let deadline = Instant::now() + wait;
+        while self.pending.load(Ordering::SeqCst) > 0 {
+            if Instant::now() >= deadline {
+                log::warn!("{}", tr!("webhook.flush_timeout"));
+                return;
+            }
+            thread::sleep(FLUSH_POLL_INTERVAL);
+        }
+    }
+}
+
+/// 发送一次通知，失败时按配置重试
+fn deliver(agent: &ureq::Agent, config: &WebhookConfig, notification: &Notification) {
+    let payload = notification.payload(config.payload.as_deref());
+    let mut backoff = Duration::from_millis(config.backoff_ms);
+    for attempt in 0..=config.retries {
+        let mut request = agent
+            .post(&config.url)
+            .set("Content-Type", "application/json");
+        for (name, value) in &config.headers {
+            request = request.set(name, value);
+        }
+        let retryable = match request.send_string(&payload) {
+            Ok(_) => {
+                log::debug!(
+                    "{}",
+                    tr!(
+                        "webhook.delivered",
+                        url = config.url,
+                        event = notification.event.name()
+                    )
+                );
+                return;
+            }
+            // 请求本身有误时重试也不会成功
+            Err(ureq::Error::Status(code, _)) => code == 429 || code >= 500,
+            Err(ureq::Error::Transport(e)) => {
+                log::debug!(
+                    "{}",
+                    tr!("webhook.attempt_failed", url = config.url, message = e)
+                );
+                true
+            }
+        };
+        if !retryable || attempt == config.retries {
+            log::warn!(
+                "{}",
+                tr!(
+                    "webhook.gave_up",
+                    url = config.url,
+                    event = notification.event.name()
+                )
+            );
+            return;
+        }
+        thread::sleep(backoff);
+        backoff *= 2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        thread::JoinHandle,
+    };
+
+    use super::*;
+
+    /// 一次收到的请求
+    struct Request {
+        head: String,
+        body: String,
+    }
+
+    /// 本地的 webhook 接收端，依次以 `statuses` 中的状态码响应，返回收到的请求
+    fn stub_server(statuses: Vec<u16>) -> (String, JoinHandle<Vec<Request>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            statuses
+                .into_iter()
+                .map(|status| {
+                    let (stream, _) = listener.accept().unwrap();
+                    let mut reader = BufReader::new(&stream);
+                    let mut head = String::new();
+                    let mut length = 0;
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        if line.trim_end().is_empty() {
+                            break;
+                        }
+                        if let Some((name, value)) = line.split_once(':') {
+                            if name.eq_ignore_ascii_case("content-length") {
+                                length = value.trim().parse().unwrap();
+                            }
+                        }
+                        head.push_str(&line);
+                    }
+                    let mut body = vec![0; length];
+                    reader.read_exact(&mut body).unwrap();
+                    let mut stream = &stream;
+                    write!(
+                        stream,
+                        "HTTP/1.1 {} Stub\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                        status
+                    )
+                    .unwrap();
+                    Request {
+                        head: head.to_ascii_lowercase(),
+                        body: String::from_utf8(body).unwrap(),
+                    }
+                })
+                .collect()
+        });
+        (url, handle)
+    }
+
+    fn repeated_error() -> Notification {
+        Notification::from_event(&PlotEvent::ErrorRepeated {
+            window: 7,
+            kind: "io",
+            message: "say \"hi\"\n".to_string(),
+            count: 3,
+        })
+        .unwrap()
+    }
+
+    fn config(url: String) -> WebhookConfig {
+        WebhookConfig {
+            url,
+            payload: Some(
+                r#"{"event":"{event}","window":{window},"message":"{message}","count":{count}}"#
+                    .to_string(),
+            ),
+            headers: BTreeMap::from([("X-Token".to_string(), "secret".to_string())]),
+            backoff_ms: 10,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn payload_template_escapes_strings() {
+        let payload = repeated_error().payload(config(String::new()).payload.as_deref());
+        let value: Value = serde_json::from_str(&payload).unwrap();
+        assert_eq!(value["event"], "repeated_error");
+        assert_eq!(value["window"], 7);
+        assert_eq!(value["message"], "say \"hi\"\n");
+        assert_eq!(value["count"], 3);
+    }
+
+    #[test]
+    fn payload_without_template_contains_all_values() {
+        let value: Value = serde_json::from_str(&repeated_error().payload(None)).unwrap();
+        for name in [
+            "event", "window", "kind", "message", "count", "text", "time",
+        ] {
+            assert!(value.get(name).is_some(), "missing {}", name);
+        }
+    }
+
+    #[test]
+    fn deliver_retries_server_errors() {
+        let (url, server) = stub_server(vec![500, 503, 200]);
+        let config = config(url);
+        let notification = repeated_error();
+        deliver(&ureq::Agent::new(), &config, &notification);
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 3);
+        let payload = notification.payload(config.payload.as_deref());
+        for request in &requests {
+            assert!(request.head.starts_with("post /hook "));
+            assert!(request.head.contains("x-token: secret"));
+            assert!(request.head.contains("content-type: application/json"));
+            assert_eq!(request.body, payload);
+        }
+    }
+
+    #[test]
+    fn deliver_gives_up_on_client_errors() {
+        let (url, server) = stub_server(vec![400, 200]);
+        deliver(&ureq::Agent::new(), &config(url.clone()), &repeated_error());
+        // 没有重试时，接收端收到的下一个请求是这里发送的
+        ureq::post(&url).send_string("marker").unwrap();
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests[1].body, "marker");
+    }
+
+    #[test]
+    fn flush_waits_for_queued_notifications() {
+        let (url, server) = stub_server(vec![200]);
+        let webhooks = Webhooks::start(vec![WebhookConfig {
+            url,
+            events: vec![WebhookEvent::DialogueEnded],
+            ..Default::default()
+        }])
+        .unwrap();
+        webhooks.observe(&PlotEvent::DialogueStarted { window: 1 });
+        webhooks.observe(&PlotEvent::DialogueEnded { window: 1 });
+        webhooks.flush(Duration::from_secs(5));
+        assert_eq!(webhooks.pending.load(Ordering::SeqCst), 0);
+
+        let requests = server.join().unwrap();
+        let value: Value = serde_json::from_str(&requests[0].body).unwrap();
+        assert_eq!(value["event"], "dialogue_ended");
+        assert_eq!(value["window"], 1);
+    }
+}
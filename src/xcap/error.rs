@@ -1,9 +1,19 @@
 use thiserror::Error;
 
+#[cfg(target_os = "windows")]
+use super::window::CaptureMethod;
+
 #[derive(Debug, Error)]
 pub enum XCapError {
     #[error("{0}")]
     Error(String),
+    /// 使用 `method` 截图时失败
+    #[cfg(target_os = "windows")]
+    #[error("{method}: {message}")]
+    CaptureFailed {
+        method: CaptureMethod,
+        message: String,
+    },
     #[cfg(target_os = "windows")]
     #[error(transparent)]
     WindowsCoreError(#[from] windows::core::Error),
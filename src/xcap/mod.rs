@@ -1,4 +1,6 @@
 mod error;
 mod window;
 
-pub use window::Window;
+pub use error::XCapError;
+pub(crate) use window::is_blank;
+pub use window::{CaptureMethod, Window};
@@ -1,13 +1,12 @@
-use image::{DynamicImage, RgbaImage};
-use std::{ffi::c_void, mem};
+use image::{imageops::FilterType, DynamicImage, RgbaImage};
+use std::{fmt, mem};
 use windows::Win32::{
     Foundation::HWND,
     Graphics::{
         Dwm::DwmIsCompositionEnabled,
         Gdi::{
-            BitBlt, CreateCompatibleBitmap, CreateCompatibleDC, GetCurrentObject, GetDIBits,
-            GetObjectW, SelectObject, BITMAP, BITMAPINFO, BITMAPINFOHEADER, DIB_RGB_COLORS,
-            OBJ_BITMAP, SRCCOPY,
+            BitBlt, CreateCompatibleBitmap, CreateCompatibleDC, GetDIBits, SelectObject,
+            BITMAPINFO, BITMAPINFOHEADER, DIB_RGB_COLORS, SRCCOPY,
         },
     },
     Storage::Xps::{PrintWindow, PRINT_WINDOW_FLAGS},
@@ -18,9 +17,62 @@ use super::error::{XCapError, XCapResult};
 
 use super::{
     boxed::{BoxHBITMAP, BoxHDC},
+    geometry::capture_geometry,
     utils::get_os_major_version,
 };
 
+/// 每个通道与第一个采样点相差不超过该值时，认为画面是纯色的
+const BLANK_TOLERANCE: u8 = 4;
+/// 判断纯色画面时每个方向上的采样点数
+const BLANK_SAMPLES: u32 = 64;
+
+/// 截图时实际使用的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureMethod {
+    /// PrintWindow(PW_RENDERFULLCONTENT)
+    PrintWindowFullContent,
+    /// PrintWindow(0)
+    PrintWindow,
+    /// PrintWindow(PW_CLIENTONLY | PW_RENDERFULLCONTENT)
+    PrintWindowClientOnly,
+    /// 从窗口的 DC BitBlt
+    WindowBitBlt,
+    /// 从桌面的 DC BitBlt 窗口所在的区域，会截到遮挡在窗口上方的内容
+    Desktop,
+}
+
+impl fmt::Display for CaptureMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CaptureMethod::PrintWindowFullContent => "PrintWindow(PW_RENDERFULLCONTENT)",
+            CaptureMethod::PrintWindow => "PrintWindow",
+            CaptureMethod::PrintWindowClientOnly => "PrintWindow(PW_CLIENTONLY)",
+            CaptureMethod::WindowBitBlt => "BitBlt",
+            CaptureMethod::Desktop => "Desktop BitBlt",
+        })
+    }
+}
+
+/// 画面是否为纯色（例如部分全屏或 HDR 模式下 PrintWindow 得到的全黑画面）
+///
+/// 只检查均匀分布的采样点，避免遍历所有像素
+pub(crate) fn is_blank(image: &RgbaImage) -> bool {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return true;
+    }
+
+    let first = image.get_pixel(0, 0);
+    let step_x = (width / BLANK_SAMPLES).max(1);
+    let step_y = (height / BLANK_SAMPLES).max(1);
+    (0..height).step_by(step_y as usize).all(|y| {
+        (0..width).step_by(step_x as usize).all(|x| {
+            let pixel = image.get_pixel(x, y);
+            (0..3).all(|i| pixel[i].abs_diff(first[i]) <= BLANK_TOLERANCE)
+        })
+    })
+}
+
 fn to_rgba_image(
     box_hdc_mem: BoxHDC,
     box_h_bitmap: BoxHBITMAP,
@@ -74,7 +126,6 @@ fn to_rgba_image(
         .ok_or_else(|| XCapError::new("RgbaImage::from_raw failed"))
 }
 
-#[allow(unused)]
 pub fn capture_monitor(x: i32, y: i32, width: i32, height: i32) -> XCapResult<RgbaImage> {
     unsafe {
         let hwnd = GetDesktopWindow();
@@ -114,34 +165,21 @@ pub fn capture_monitor(x: i32, y: i32, width: i32, height: i32) -> XCapResult<Rg
 #[allow(unused)]
 pub fn capture_window(
     hwnd: HWND,
-    scale_factor: f32,
+    window_dpi: u32,
+    monitor_dpi: u32,
     window_info: &WINDOWINFO,
-) -> XCapResult<RgbaImage> {
+) -> XCapResult<(RgbaImage, CaptureMethod)> {
     unsafe {
         let box_hdc_window: BoxHDC = BoxHDC::from(hwnd);
-        let rc_window = window_info.rcWindow;
-
-        let mut width = rc_window.right - rc_window.left;
-        let mut height = rc_window.bottom - rc_window.top;
-
-        let hgdi_obj = GetCurrentObject(*box_hdc_window, OBJ_BITMAP);
-        let mut bitmap = BITMAP::default();
 
-        let mut horizontal_scale = 1.0;
-        let mut vertical_scale = 1.0;
-
-        if GetObjectW(
-            hgdi_obj,
-            mem::size_of::<BITMAP>() as i32,
-            Some(&mut bitmap as *mut BITMAP as *mut c_void),
-        ) != 0
-        {
-            width = bitmap.bmWidth;
-            height = bitmap.bmHeight;
-        }
-
-        width = (width as f32 * scale_factor).ceil() as i32;
-        height = (height as f32 * scale_factor).ceil() as i32;
+        let geometry = capture_geometry(
+            window_info.rcWindow.into(),
+            window_info.rcClient.into(),
+            window_dpi,
+            monitor_dpi,
+        );
+        let width = geometry.bitmap_width;
+        let height = geometry.bitmap_height;
 
         // 内存中的HDC，使用 DeleteDC 函数释放
         // https://learn.microsoft.com/zh-cn/windows/win32/api/wingdi/nf-wingdi-createcompatibledc
@@ -151,6 +189,7 @@ pub fn capture_window(
         let previous_object = SelectObject(*box_hdc_mem, *box_h_bitmap);
 
         let mut is_success = false;
+        let mut method = CaptureMethod::PrintWindowFullContent;
 
         // https://webrtc.googlesource.com/src.git/+/refs/heads/main/modules/desktop_capture/win/window_capturer_win_gdi.cc#301
         if get_os_major_version() >= 8 {
@@ -158,14 +197,17 @@ pub fn capture_window(
         }
 
         if !is_success && DwmIsCompositionEnabled()?.as_bool() {
+            method = CaptureMethod::PrintWindow;
             is_success = PrintWindow(hwnd, *box_hdc_mem, PRINT_WINDOW_FLAGS(0)).as_bool();
         }
 
         if !is_success {
+            method = CaptureMethod::PrintWindowClientOnly;
             is_success = PrintWindow(hwnd, *box_hdc_mem, PRINT_WINDOW_FLAGS(3)).as_bool();
         }
 
         if !is_success {
+            method = CaptureMethod::WindowBitBlt;
             is_success = BitBlt(
                 *box_hdc_mem,
                 0,
@@ -182,17 +224,32 @@ pub fn capture_window(
 
         SelectObject(*box_hdc_mem, previous_object);
 
-        let image = to_rgba_image(box_hdc_mem, box_h_bitmap, width, height)?;
-
-        let mut rc_client = window_info.rcClient;
-
-        let x = ((rc_client.left - rc_window.left) as f32 * scale_factor).ceil();
-        let y = ((rc_client.top - rc_window.top) as f32 * scale_factor).ceil();
-        let w = ((rc_client.right - rc_client.left) as f32 * scale_factor).floor();
-        let h = ((rc_client.bottom - rc_client.top) as f32 * scale_factor).floor();
-
-        Ok(DynamicImage::ImageRgba8(image)
-            .crop(x as u32, y as u32, w as u32, h as u32)
-            .to_rgba8())
+        let image = to_rgba_image(box_hdc_mem, box_h_bitmap, width, height).map_err(|e| {
+            XCapError::CaptureFailed {
+                method,
+                message: e.to_string(),
+            }
+        })?;
+
+        let client = DynamicImage::ImageRgba8(image).crop(
+            geometry.crop_x,
+            geometry.crop_y,
+            geometry.crop_width,
+            geometry.crop_height,
+        );
+
+        // 窗口不支持当前显示器的 DPI 时，位图比屏幕上小（或大），缩放到屏幕上的大小
+        let image = if geometry.needs_resize() {
+            client
+                .resize_exact(
+                    geometry.output_width,
+                    geometry.output_height,
+                    FilterType::Triangle,
+                )
+                .to_rgba8()
+        } else {
+            client.to_rgba8()
+        };
+        Ok((image, method))
     }
 }
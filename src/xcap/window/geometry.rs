@@ -0,0 +1,240 @@
+use windows::Win32::Foundation::RECT;
+
+/// 屏幕上的矩形，与 `RECT` 相同，单位为物理像素
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct Rect {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl Rect {
+    pub fn width(&self) -> i32 {
+        (self.right - self.left).max(0)
+    }
+
+    pub fn height(&self) -> i32 {
+        (self.bottom - self.top).max(0)
+    }
+}
+
+impl From<RECT> for Rect {
+    fn from(rect: RECT) -> Self {
+        Self {
+            left: rect.left,
+            top: rect.top,
+            right: rect.right,
+            bottom: rect.bottom,
+        }
+    }
+}
+
+/// 截取一个窗口时的几何信息
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct CaptureGeometry {
+    /// `PrintWindow` 绘制整个窗口所需的位图大小，单位为窗口自身的像素
+    pub bitmap_width: i32,
+    pub bitmap_height: i32,
+    /// 客户区在位图中的位置和大小
+    pub crop_x: u32,
+    pub crop_y: u32,
+    pub crop_width: u32,
+    pub crop_height: u32,
+    /// 最终图片的大小，即客户区在屏幕上的物理像素大小
+    pub output_width: u32,
+    pub output_height: u32,
+}
+
+impl CaptureGeometry {
+    /// 裁剪出的客户区需要缩放才能与屏幕上的大小一致
+    pub fn needs_resize(&self) -> bool {
+        self.crop_width != self.output_width || self.crop_height != self.output_height
+    }
+}
+
+/// 计算截图的位图大小和客户区的裁剪区域
+///
+/// `window_rect` 和 `client_rect` 为屏幕坐标（进程需要是 per-monitor DPI aware 的），
+/// `window_dpi` 为窗口自身绘制时使用的 DPI（`GetDpiForWindow`），`monitor_dpi` 为窗口所在显示器的 DPI。
+/// 不支持 DPI 缩放的窗口按自身的 DPI 绘制，再由系统拉伸到显示器上，
+/// 因此 `PrintWindow` 得到的位图是屏幕上大小的 `window_dpi / monitor_dpi` 倍。
+/// DPI 为 0（获取失败）时视为与另一方相同
+pub(crate) fn capture_geometry(
+    window_rect: Rect,
+    client_rect: Rect,
+    window_dpi: u32,
+    monitor_dpi: u32,
+) -> CaptureGeometry {
+    let scale = match (window_dpi, monitor_dpi) {
+        (0, _) | (_, 0) => 1.0,
+        (window_dpi, monitor_dpi) => window_dpi as f64 / monitor_dpi as f64,
+    };
+    let scaled = |value: i32| value as f64 * scale;
+
+    let bitmap_width = scaled(window_rect.width()).ceil().max(1.0) as i32;
+    let bitmap_height = scaled(window_rect.height()).ceil().max(1.0) as i32;
+
+    // 客户区可能部分位于窗口之外（例如最大化时的边框），限制在位图内
+    let crop_x = scaled(client_rect.left - window_rect.left)
+        .round()
+        .clamp(0.0, (bitmap_width - 1) as f64) as u32;
+    let crop_y = scaled(client_rect.top - window_rect.top)
+        .round()
+        .clamp(0.0, (bitmap_height - 1) as f64) as u32;
+    let crop_width =
+        (scaled(client_rect.width()).round() as u32).clamp(1, bitmap_width as u32 - crop_x);
+    let crop_height =
+        (scaled(client_rect.height()).round() as u32).clamp(1, bitmap_height as u32 - crop_y);
+
+    CaptureGeometry {
+        bitmap_width,
+        bitmap_height,
+        crop_x,
+        crop_y,
+        crop_width,
+        crop_height,
+        output_width: client_rect.width().max(1) as u32,
+        output_height: client_rect.height().max(1) as u32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(left: i32, top: i32, right: i32, bottom: i32) -> Rect {
+        Rect {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    /// 1000x700 的窗口，标题栏 31、边框 8 个像素
+    const WINDOW: Rect = Rect {
+        left: 100,
+        top: 100,
+        right: 1100,
+        bottom: 800,
+    };
+    const CLIENT: Rect = Rect {
+        left: 108,
+        top: 131,
+        right: 1092,
+        bottom: 792,
+    };
+
+    #[test]
+    fn same_dpi_crops_client_area() {
+        for dpi in [96, 144, 192] {
+            let geometry = capture_geometry(WINDOW, CLIENT, dpi, dpi);
+            assert_eq!(
+                geometry,
+                CaptureGeometry {
+                    bitmap_width: 1000,
+                    bitmap_height: 700,
+                    crop_x: 8,
+                    crop_y: 31,
+                    crop_width: 984,
+                    crop_height: 661,
+                    output_width: 984,
+                    output_height: 661,
+                },
+                "dpi {}",
+                dpi
+            );
+            assert!(!geometry.needs_resize());
+        }
+    }
+
+    #[test]
+    fn dpi_unaware_window_on_150_percent_monitor() {
+        // 系统把 96 DPI 下 1000x700 的窗口拉伸为 1500x1050
+        let geometry = capture_geometry(rect(0, 0, 1500, 1050), rect(12, 45, 1488, 1038), 96, 144);
+        assert_eq!((geometry.bitmap_width, geometry.bitmap_height), (1000, 700));
+        assert_eq!(
+            (
+                geometry.crop_x,
+                geometry.crop_y,
+                geometry.crop_width,
+                geometry.crop_height
+            ),
+            (8, 30, 984, 662)
+        );
+        assert_eq!((geometry.output_width, geometry.output_height), (1476, 993));
+        assert!(geometry.needs_resize());
+    }
+
+    #[test]
+    fn dpi_unaware_window_on_200_percent_monitor() {
+        let geometry = capture_geometry(rect(0, 0, 2000, 1400), rect(16, 62, 1984, 1384), 96, 192);
+        assert_eq!((geometry.bitmap_width, geometry.bitmap_height), (1000, 700));
+        assert_eq!(
+            (
+                geometry.crop_x,
+                geometry.crop_y,
+                geometry.crop_width,
+                geometry.crop_height
+            ),
+            (8, 31, 984, 661)
+        );
+        assert_eq!(
+            (geometry.output_width, geometry.output_height),
+            (1968, 1322)
+        );
+        assert!(geometry.needs_resize());
+    }
+
+    #[test]
+    fn window_on_secondary_monitor_with_negative_coordinates() {
+        // 位于主显示器左上方的显示器上
+        let geometry = capture_geometry(
+            rect(-1920, -900, -920, -200),
+            rect(-1912, -869, -928, -208),
+            144,
+            144,
+        );
+        assert_eq!((geometry.bitmap_width, geometry.bitmap_height), (1000, 700));
+        assert_eq!((geometry.crop_x, geometry.crop_y), (8, 31));
+        assert_eq!((geometry.crop_width, geometry.crop_height), (984, 661));
+
+        // 不支持 DPI 缩放的窗口位于 150% 的副显示器上
+        let geometry = capture_geometry(
+            rect(-1500, -300, 0, 750),
+            rect(-1488, -255, -12, 738),
+            96,
+            144,
+        );
+        assert_eq!((geometry.bitmap_width, geometry.bitmap_height), (1000, 700));
+        assert_eq!((geometry.crop_x, geometry.crop_y), (8, 30));
+        assert_eq!((geometry.crop_width, geometry.crop_height), (984, 662));
+        assert_eq!((geometry.output_width, geometry.output_height), (1476, 993));
+    }
+
+    #[test]
+    fn maximized_client_area_is_clamped_to_bitmap() {
+        // 客户区左右和下方超出了窗口矩形
+        let geometry = capture_geometry(rect(0, 0, 1920, 1040), rect(-8, 23, 1928, 1080), 96, 96);
+        assert_eq!(
+            (geometry.bitmap_width, geometry.bitmap_height),
+            (1920, 1040)
+        );
+        assert_eq!((geometry.crop_x, geometry.crop_y), (0, 23));
+        assert_eq!((geometry.crop_width, geometry.crop_height), (1920, 1017));
+        assert_eq!(
+            (geometry.output_width, geometry.output_height),
+            (1936, 1057)
+        );
+        assert!(geometry.needs_resize());
+    }
+
+    #[test]
+    fn zero_dpi_is_treated_as_equal() {
+        let expected = capture_geometry(WINDOW, CLIENT, 96, 96);
+        assert_eq!(capture_geometry(WINDOW, CLIENT, 0, 144), expected);
+        assert_eq!(capture_geometry(WINDOW, CLIENT, 144, 0), expected);
+        assert_eq!(capture_geometry(WINDOW, CLIENT, 0, 0), expected);
+    }
+}
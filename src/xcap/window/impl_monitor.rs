@@ -1,22 +1,21 @@
 use std::mem;
-use windows::Win32::Graphics::Gdi::{
-    GetDeviceCaps, GetMonitorInfoW, DESKTOPHORZRES, HMONITOR, HORZRES, MONITORINFO, MONITORINFOEXW,
+use windows::Win32::{
+    Graphics::Gdi::{GetMonitorInfoW, HMONITOR, MONITORINFO, MONITORINFOEXW},
+    UI::HiDpi::{GetDpiForMonitor, MDT_EFFECTIVE_DPI},
 };
 
 use crate::xcap::error::XCapResult;
 
-use super::boxed::BoxHDC;
-
 // A 函数与 W 函数区别
 // https://learn.microsoft.com/zh-cn/windows/win32/learnwin32/working-with-strings
 
 #[derive(Debug, Clone)]
 pub(crate) struct ImplMonitor {
-    #[allow(unused)]
     pub hmonitor: HMONITOR,
     #[allow(unused)]
     pub monitor_info_ex_w: MONITORINFOEXW,
-    pub scale_factor: f32,
+    /// 显示器的有效 DPI，100% 缩放时为 96
+    pub dpi: u32,
 }
 
 impl ImplMonitor {
@@ -29,19 +28,15 @@ impl ImplMonitor {
         // https://learn.microsoft.com/zh-cn/windows/win32/api/winuser/nf-winuser-getmonitorinfoa
         unsafe { GetMonitorInfoW(hmonitor, monitor_info_ex_w_ptr).ok()? };
 
-        let box_hdc_monitor = BoxHDC::from(&monitor_info_ex_w.szDevice);
-
-        let scale_factor = unsafe {
-            let physical_width = GetDeviceCaps(*box_hdc_monitor, DESKTOPHORZRES);
-            let logical_width = GetDeviceCaps(*box_hdc_monitor, HORZRES);
-
-            physical_width as f32 / logical_width as f32
-        };
+        // https://learn.microsoft.com/zh-cn/windows/win32/api/shellscalingapi/nf-shellscalingapi-getdpiformonitor
+        let mut dpi_x = 0;
+        let mut dpi_y = 0;
+        unsafe { GetDpiForMonitor(hmonitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y)? };
 
         Ok(ImplMonitor {
             hmonitor,
             monitor_info_ex_w,
-            scale_factor,
+            dpi: dpi_x,
         })
     }
 }
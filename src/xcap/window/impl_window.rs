@@ -7,15 +7,20 @@ use windows::Win32::{
         Gdi::{IsRectEmpty, MonitorFromWindow, MONITOR_DEFAULTTONEAREST},
     },
     System::Threading::GetCurrentProcessId,
+    UI::HiDpi::GetDpiForWindow,
     UI::WindowsAndMessaging::{
         EnumWindows, GetClassNameW, GetForegroundWindow, GetWindowInfo, GetWindowLongPtrW,
-        GetWindowTextLengthW, GetWindowTextW, GetWindowThreadProcessId, IsWindow, IsWindowVisible,
-        GWL_EXSTYLE, WINDOWINFO, WINDOW_EX_STYLE, WS_EX_TOOLWINDOW,
+        GetWindowTextLengthW, GetWindowTextW, GetWindowThreadProcessId, IsIconic, IsWindow,
+        IsWindowVisible, GWL_EXSTYLE, WINDOWINFO, WINDOW_EX_STYLE, WS_EX_TOOLWINDOW,
     },
 };
 
-use super::error::XCapResult;
-use super::{capture::capture_window, impl_monitor::ImplMonitor, utils::wide_string_to_string};
+use super::error::{XCapError, XCapResult};
+use super::{
+    capture::{capture_monitor, capture_window, CaptureMethod},
+    impl_monitor::ImplMonitor,
+    utils::{set_dpi_awareness, wide_string_to_string},
+};
 
 #[derive(Debug, Clone)]
 pub(crate) struct ImplWindow {
@@ -23,12 +28,16 @@ pub(crate) struct ImplWindow {
     #[allow(unused)]
     pub window_info: WINDOWINFO,
     pub title: String,
+    pub pid: u32,
     pub current_monitor: ImplMonitor,
+    /// 窗口自身绘制时使用的 DPI，不支持 DPI 缩放的窗口为 96
+    pub dpi: u32,
     pub x: i32,
     pub y: i32,
     pub width: u32,
     pub height: u32,
     pub is_active: bool,
+    pub is_minimized: bool,
 }
 
 fn is_window_cloaked(hwnd: HWND) -> bool {
@@ -181,6 +190,7 @@ impl ImplWindow {
             GetWindowInfo(hwnd, &mut window_info)?;
 
             let title = get_window_title(hwnd)?;
+            let pid = get_process_id(hwnd);
             let hmonitor = MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST);
             let rc_client = window_info.rcClient;
             let active = GetForegroundWindow();
@@ -190,18 +200,73 @@ impl ImplWindow {
                 hwnd,
                 window_info,
                 title,
+                pid,
                 current_monitor: ImplMonitor::new(hmonitor)?,
+                dpi: GetDpiForWindow(hwnd),
                 x: rc_client.left,
                 y: rc_client.top,
                 width: (rc_client.right - rc_client.left) as u32,
                 height: (rc_client.bottom - rc_client.top) as u32,
 
                 is_active,
+                is_minimized: IsIconic(hwnd).as_bool(),
             })
         }
     }
 
+    /// 根据窗口句柄创建，句柄无效时返回错误
+    pub fn from_hwnd(hwnd: HWND) -> XCapResult<ImplWindow> {
+        set_dpi_awareness();
+        if !unsafe { IsWindow(hwnd) }.as_bool() {
+            return Err(XCapError::new("Invalid window handle"));
+        }
+        ImplWindow::new(hwnd)
+    }
+
+    /// 当前的前台窗口
+    pub fn foreground() -> Option<ImplWindow> {
+        set_dpi_awareness();
+        let hwnd = unsafe { GetForegroundWindow() };
+        if hwnd.is_invalid() || !is_valid_window(hwnd) {
+            return None;
+        }
+        ImplWindow::new(hwnd).ok()
+    }
+
+    /// 重新读取标题、位置和前台状态，窗口已关闭（或句柄被其他进程复用）时返回 `false`
+    ///
+    /// 只在窗口移动到其他显示器时才重新创建 `ImplMonitor`
+    pub fn refresh(&mut self) -> XCapResult<bool> {
+        unsafe {
+            if !IsWindow(self.hwnd).as_bool()
+                || !IsWindowVisible(self.hwnd).as_bool()
+                || get_process_id(self.hwnd) != self.pid
+            {
+                return Ok(false);
+            }
+
+            GetWindowInfo(self.hwnd, &mut self.window_info)?;
+            self.dpi = GetDpiForWindow(self.hwnd);
+            self.title = get_window_title(self.hwnd)?;
+
+            let hmonitor = MonitorFromWindow(self.hwnd, MONITOR_DEFAULTTONEAREST);
+            if hmonitor != self.current_monitor.hmonitor {
+                self.current_monitor = ImplMonitor::new(hmonitor)?;
+            }
+
+            let rc_client = self.window_info.rcClient;
+            self.x = rc_client.left;
+            self.y = rc_client.top;
+            self.width = (rc_client.right - rc_client.left) as u32;
+            self.height = (rc_client.bottom - rc_client.top) as u32;
+            self.is_active = GetForegroundWindow() == self.hwnd;
+            self.is_minimized = IsIconic(self.hwnd).as_bool();
+        }
+        Ok(true)
+    }
+
     pub fn all() -> XCapResult<Vec<ImplWindow>> {
+        set_dpi_awareness();
         let hwnds_mut_ptr: *mut Vec<HWND> = Box::into_raw(Box::default());
 
         let hwnds = unsafe {
@@ -224,12 +289,24 @@ impl ImplWindow {
 }
 
 impl ImplWindow {
-    pub fn capture_image(&self) -> XCapResult<RgbaImage> {
-        // TODO: 在win10之后，不同窗口有不同的dpi，所以可能存在截图不全或者截图有较大空白，实际窗口没有填充满图片
+    pub fn capture_image(&self) -> XCapResult<(RgbaImage, CaptureMethod)> {
+        // 在win10之后，不同窗口有不同的dpi，由 capture_geometry 根据窗口和显示器的dpi计算截图区域
         capture_window(
             self.hwnd,
-            self.current_monitor.scale_factor,
+            self.dpi,
+            self.current_monitor.dpi,
             &self.window_info,
         )
     }
+
+    /// 截取屏幕上客户区所在的区域，会截到遮挡在窗口上方的内容
+    pub fn capture_desktop(&self) -> XCapResult<RgbaImage> {
+        let rc_client = self.window_info.rcClient;
+        capture_monitor(
+            rc_client.left,
+            rc_client.top,
+            rc_client.right - rc_client.left,
+            rc_client.bottom - rc_client.top,
+        )
+    }
 }
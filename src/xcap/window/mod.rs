@@ -1,13 +1,18 @@
 mod boxed;
 mod capture;
+mod geometry;
 mod impl_monitor;
 mod impl_window;
 mod utils;
 use image::RgbaImage;
+use windows::Win32::Foundation::HWND;
 
 use error::XCapResult;
 use impl_window::ImplWindow;
 
+pub(crate) use capture::is_blank;
+pub use capture::CaptureMethod;
+
 use super::error;
 
 #[derive(Debug, Clone)]
@@ -15,6 +20,9 @@ pub struct Window {
     pub(crate) impl_window: ImplWindow,
 }
 
+// 窗口句柄和显示器句柄在整个系统内有效，不与创建它们的线程绑定
+unsafe impl Send for Window {}
+
 impl Window {
     pub(crate) fn new(impl_window: ImplWindow) -> Window {
         Window { impl_window }
@@ -30,13 +38,37 @@ impl Window {
 
         Ok(windows)
     }
+
+    /// Create a window from its id (HWND), fails if the handle is no longer valid.
+    pub fn from_id(id: isize) -> XCapResult<Window> {
+        ImplWindow::from_hwnd(HWND(id as _)).map(Window::new)
+    }
+
+    /// The foreground window, if it is a capturable window.
+    pub fn foreground() -> Option<Window> {
+        ImplWindow::foreground().map(Window::new)
+    }
+
+    /// Re-read the window's title, position and foreground state.
+    /// Returns `false` if the window no longer exists.
+    pub fn refresh(&mut self) -> XCapResult<bool> {
+        self.impl_window.refresh()
+    }
 }
 
 impl Window {
+    /// The window id (HWND).
+    pub fn id(&self) -> isize {
+        self.impl_window.hwnd.0 as isize
+    }
     /// The window title
     pub fn title(&self) -> &str {
         &self.impl_window.title
     }
+    /// The id of the process that owns the window.
+    pub fn pid(&self) -> u32 {
+        self.impl_window.pid
+    }
 
     /// The window x coordinate.
     pub fn x(&self) -> i32 {
@@ -57,10 +89,19 @@ impl Window {
     pub fn is_active(&self) -> bool {
         self.impl_window.is_active
     }
+    pub fn is_minimized(&self) -> bool {
+        self.impl_window.is_minimized
+    }
 }
 
 impl Window {
-    pub fn capture_image(&self) -> XCapResult<RgbaImage> {
+    /// Capture the client area from the window itself, even when it is covered.
+    pub fn capture_image(&self) -> XCapResult<(RgbaImage, CaptureMethod)> {
         self.impl_window.capture_image()
     }
+
+    /// Capture the screen area of the client area, including anything covering it.
+    pub fn capture_desktop(&self) -> XCapResult<RgbaImage> {
+        self.impl_window.capture_desktop()
+    }
 }
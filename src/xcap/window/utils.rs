@@ -1,4 +1,9 @@
+use std::sync::Once;
+
 use sysinfo::System;
+use windows::Win32::UI::HiDpi::{
+    SetProcessDpiAwarenessContext, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2,
+};
 
 use super::error::XCapResult;
 
@@ -20,3 +25,15 @@ pub(super) fn get_os_major_version() -> u8 {
         })
         .unwrap_or(0)
 }
+
+/// 让窗口坐标、截图和鼠标坐标都使用物理像素，只在第一次调用时设置
+///
+/// 已经通过 manifest 等方式设置过时会失败，此时保持原来的设置
+pub(super) fn set_dpi_awareness() {
+    static ONCE: Once = Once::new();
+    ONCE.call_once(|| unsafe {
+        if let Err(e) = SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2) {
+            log::debug!("SetProcessDpiAwarenessContext failed: {}", e);
+        }
+    });
+}